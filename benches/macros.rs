@@ -0,0 +1,64 @@
+//! Macro expansion of the examples together with the standard library. Run
+//! with `cargo bench --bench macros`.
+
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use orchidlang::error::Reporter;
+use orchidlang::facade::loader::Loader;
+use orchidlang::facade::macro_runner::MacroRunner;
+use orchidlang::libs::asynch::system::AsynchSystem;
+use orchidlang::libs::directfs::DirectFS;
+use orchidlang::libs::io::{IOService, Stream};
+use orchidlang::libs::scheduler::system::SeqScheduler;
+use orchidlang::libs::std::std_system::StdConfig;
+use orchidlang::sym;
+
+const EXAMPLES: &[&str] = &[
+  "calculator",
+  "file-browser",
+  "fizz-buzz",
+  "hello-world",
+  "list-processing",
+  "maps",
+  "match",
+  "protocol",
+  "yes",
+];
+
+fn with_env<T>(cb: impl for<'a> FnOnce(Loader<'a>) -> T) -> T {
+  let mut asynch = AsynchSystem::new();
+  let scheduler = SeqScheduler::new(&mut asynch);
+  let std_streams = [
+    ("stdin", Stream::Source(BufReader::new(Box::new(io::empty())))),
+    ("stdout", Stream::Sink(Box::new(io::sink()))),
+    ("stderr", Stream::Sink(Box::new(io::sink()))),
+  ];
+  let env = Loader::new()
+    .add_system(StdConfig { impure: true })
+    .add_system(asynch)
+    .add_system(scheduler.clone())
+    .add_system(IOService::new(scheduler.clone(), std_streams))
+    .add_system(DirectFS::new(scheduler));
+  cb(env)
+}
+
+fn expand_examples(c: &mut Criterion) {
+  let mut group = c.benchmark_group("expand");
+  group.sample_size(10);
+  for example in EXAMPLES {
+    with_env(|env| {
+      let reporter = Reporter::new();
+      let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples").join(example);
+      let tree = env.load_main(dir, [sym!(tree::main::main)], &reporter);
+      let runner = MacroRunner::new(&tree, env.proc_macros(), Some(10_000), &reporter);
+      reporter.assert_exit();
+      group.bench_function(*example, |b| b.iter(|| runner.run_macros(tree.clone(), &reporter)));
+    })
+  }
+  group.finish();
+}
+
+criterion_group!(benches, expand_examples);
+criterion_main!(benches);
@@ -5,12 +5,13 @@ use orchidlang::libs::std::exit_status::OrcExitStatus;
 use orchidlang::location::{CodeGenInfo, CodeLocation};
 use orchidlang::name::Sym;
 use orchidlang::pipeline::project::{ItemKind, ProjItem, ProjectTree};
+use orchidlang::rule::proc_macro::ProcMacro;
 use orchidlang::sym;
 
 use crate::cli::cmd_prompt;
 
 /// A little utility to step through the reproject of a macro set
-pub fn main(tree: ProjectTree, symbol: Sym) -> OrcExitStatus {
+pub fn main(tree: ProjectTree, procs: Vec<ProcMacro>, symbol: Sym) -> OrcExitStatus {
   print!("Macro debugger starting on {symbol}");
   let location = CodeLocation::new_gen(CodeGenInfo::no_details(sym!(orcx::macro_runner)));
   let expr_ent = match tree.0.walk1_ref(&[], &symbol[..], |_| true) {
@@ -28,7 +29,7 @@ pub fn main(tree: ProjectTree, symbol: Sym) -> OrcExitStatus {
     },
   };
   let reporter = Reporter::new();
-  let macro_runner = MacroRunner::new(&tree, None, &reporter);
+  let macro_runner = MacroRunner::new(&tree, procs, None, &reporter);
   reporter.assert_exit();
   println!("\nInitial state: {expr}");
   // print_for_debug(&code);
@@ -38,9 +39,9 @@ pub fn main(tree: ProjectTree, symbol: Sym) -> OrcExitStatus {
     match cmd.trim() {
       "" | "n" | "next" => match steps.next() {
         None => print!("Halted"),
-        Some((idx, c)) => {
+        Some((idx, (c, trace))) => {
           expr = c;
-          print!("Step {idx}: {expr}");
+          print!("Step {idx}: {expr}\nby {trace}");
         },
       },
       "p" | "print" => {
@@ -52,7 +53,7 @@ pub fn main(tree: ProjectTree, symbol: Sym) -> OrcExitStatus {
       "q" | "quit" => return OrcExitStatus::Success,
       "complete" => {
         match steps.last() {
-          Some((idx, c)) => print!("Step {idx}: {c}"),
+          Some((idx, (c, _))) => print!("Step {idx}: {c}"),
           None => print!("Already halted"),
         }
         return OrcExitStatus::Success;
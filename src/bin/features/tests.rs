@@ -15,6 +15,7 @@ use orchidlang::interpreter::error::RunError;
 use orchidlang::interpreter::nort;
 use orchidlang::libs::io::{Sink, Source};
 use orchidlang::libs::std::exit_status::OrcExitStatus;
+use orchidlang::libs::std::macro_assert::check_macro_asserts;
 use orchidlang::name::Sym;
 use rayon::iter::ParallelIterator;
 use rayon::slice::ParallelSlice;
@@ -67,25 +68,23 @@ pub fn run_tests(
     reporter.bind()
   })?;
   let threads = threads.unwrap_or_else(worker_cnt);
-  rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().unwrap();
-  let batch_size = tests.len().div_ceil(threads);
-  let errors = (tests.par_chunks(batch_size))
-    .map(|tests| {
-      with_mock_env(|env| {
-        let reporter = Reporter::new();
-        let mut proc = env.proc_dir(dir.to_owned(), true, Some(macro_limit), &reporter);
-        reporter.assert(); // checked above
-        (tests.iter())
-          .filter_map(|(test, constant)| {
-            Some((test.clone(), run_test(&mut proc, test.clone(), constant.clone()).err()?))
-          })
-          .collect_vec()
-      })
+  let run_batch = |tests: &[(Sym, NortConst)]| {
+    with_mock_env(|env| {
+      let reporter = Reporter::new();
+      let mut proc = env.proc_dir(dir.to_owned(), true, Some(macro_limit), &reporter);
+      reporter.assert(); // checked above
+      (tests.iter())
+        .filter_map(|(test, constant)| {
+          Some((test.clone(), run_test(&mut proc, test.clone(), constant.clone()).err()?))
+        })
+        .collect_vec()
     })
-    .collect::<Vec<_>>()
-    .into_iter()
-    .flatten()
-    .collect::<HashMap<_, _>>();
+  };
+  // a local pool so that the thread count applies to the tests only
+  let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+  let batch_size = tests.len().div_ceil(threads).max(1);
+  let batches = pool.install(|| tests.par_chunks(batch_size).map(run_batch).collect::<Vec<_>>());
+  let errors = batches.into_iter().flatten().collect::<HashMap<_, _>>();
   if errors.is_empty() { Ok(()) } else { Err(TestsFailed(errors).pack()) }
 }
 
@@ -102,8 +101,11 @@ impl ProjectError for TestsFailed {
 pub fn get_tree_tests(dir: &Path, reporter: &Reporter) -> ProjectResult<Vec<(Sym, NortConst)>> {
   with_mock_env(|env| {
     let tree = env.load_dir(dir.to_owned(), reporter);
-    let tree = MacroRunner::new(&tree, Some(10_000), reporter).run_macros(tree, reporter);
-    (tree.all_consts().into_iter())
+    let mr = MacroRunner::new(&tree, env.proc_macros(), Some(10_000), reporter);
+    let tree = mr.run_macros(tree, reporter);
+    let consts = tree.all_consts();
+    check_macro_asserts(&consts, reporter);
+    (consts.into_iter())
       .filter(|(_, rep)| rep.comments.iter().any(|s| s.trim() == "test"))
       .map(|(k, v)| Ok((k.clone(), NortConst::convert_from(v, reporter))))
       .collect::<ProjectResult<Vec<_>>>()
@@ -20,11 +20,13 @@ use orchidlang::gen::traits::Gen;
 use orchidlang::interpreter::gen_nort::nort_gen;
 use orchidlang::interpreter::nort::{self};
 use orchidlang::libs::std::exit_status::OrcExitStatus;
+use orchidlang::libs::std::macro_assert::check_macro_asserts;
 use orchidlang::libs::std::string::OrcString;
 use orchidlang::location::{CodeGenInfo, CodeLocation, SourceRange};
 use orchidlang::name::Sym;
 use orchidlang::parse::context::FlatLocContext;
 use orchidlang::parse::lexer::{lex, Lexeme};
+use orchidlang::rule::repository::RuleOrigin;
 use orchidlang::sym;
 use orchidlang::tree::{ModMemberRef, TreeTransforms};
 use orchidlang::virt_fs::{decl_file, DeclTree};
@@ -36,7 +38,8 @@ use crate::features::tests::{get_tree_tests, mock_source, run_test, run_tests, w
 
 #[derive(Subcommand, Debug)]
 enum Command {
-  /// Run unit tests, any constant annotated --[[ test ]]--
+  /// Run unit tests, any constant annotated --[[ test ]]--, and check
+  /// macro_assert lines
   Test {
     /// Specify an exact test to run
     #[arg(long)]
@@ -52,6 +55,12 @@ enum Command {
     symbol: String,
   },
   ListMacros,
+  /// Warn about rules of equal priority that match overlapping sequences
+  LintMacros {
+    /// Also report pairs in which neither rule comes from the project
+    #[arg(long, default_value_t = false)]
+    all: bool,
+  },
   ProjectTree {
     #[arg(long, default_value_t = false)]
     hide_locations: bool,
@@ -126,10 +135,23 @@ pub fn main() -> ExitCode {
   match args.command {
     Some(Command::ListMacros) => with_mock_env(|env| {
       let tree = env.load_main(dir, [main], &reporter);
-      let mr = MacroRunner::new(&tree, None, &reporter);
+      let mr = MacroRunner::new(&tree, env.proc_macros(), None, &reporter);
       println!("Parsed rules: {}", mr.repo);
       ExitCode::SUCCESS
     }),
+    Some(Command::LintMacros { all }) => with_mock_env(|env| {
+      let tree = env.load_main(dir, [main], &reporter);
+      let mr = MacroRunner::new(&tree, env.proc_macros(), None, &reporter);
+      reporter.assert_exit();
+      // the project is mounted at `tree`, see Loader::make_dir_fs
+      let in_project = |r: &RuleOrigin| r.location.path().first().is_some_and(|t| **t == "tree");
+      let warnings = mr.repo.lint(|r| all || in_project(r));
+      for warning in warnings.iter() {
+        eprintln!("Warning: {warning}")
+      }
+      println!("{} overlapping rule pairs found", warnings.len());
+      ExitCode::SUCCESS
+    }),
     Some(Command::ProjectTree { hide_locations, width }) => {
       let tree = with_mock_env(|env| env.load_main(dir, [main], &reporter));
       let w = width.or_else(|| termsize::get().map(|s| s.cols)).unwrap_or(74);
@@ -140,7 +162,7 @@ pub fn main() -> ExitCode {
     Some(Command::MacroDebug { symbol }) => with_mock_env(|env| {
       let tree = env.load_main(dir, [main], &reporter);
       let symbol = Sym::parse(&symbol).expect("macro-debug needs an argument");
-      macro_debug::main(tree, symbol).code()
+      macro_debug::main(tree, env.proc_macros(), symbol).code()
     }),
     Some(Command::Test { only: Some(_), threads: Some(_), .. }) => {
       eprintln!(
@@ -166,7 +188,7 @@ pub fn main() -> ExitCode {
       with_env(mock_source(), stdout_sink(), stderr_sink(), |env| {
         // iife in lieu of try blocks
         let tree = env.load_main(dir.clone(), [symbol.clone()], &reporter);
-        let mr = MacroRunner::new(&tree, Some(args.macro_limit), &reporter);
+        let mr = MacroRunner::new(&tree, env.proc_macros(), Some(args.macro_limit), &reporter);
         let consts = mr.run_macros(tree, &reporter).all_consts();
         let test = consts.get(&symbol).expect("Test not found");
         let nc = NortConst::convert_from(test.clone(), &reporter);
@@ -194,13 +216,16 @@ pub fn main() -> ExitCode {
       let in_subtrees = |sym: Sym| subtrees.iter().any(|sub| sym[..].starts_with(&sub[..]));
       let tests = with_mock_env(|env| {
         let tree = env.load_main(dir.clone(), [main.clone()], &reporter);
-        let mr = MacroRunner::new(&tree, Some(args.macro_limit), &reporter);
+        let mr = MacroRunner::new(&tree, env.proc_macros(), Some(args.macro_limit), &reporter);
         let src_consts = mr.run_macros(tree, &reporter).all_consts();
+        let own_consts = src_consts.iter().filter(|(k, _)| in_subtrees((*k).clone()));
+        check_macro_asserts(&own_consts.map(|(k, v)| (k.clone(), v.clone())).collect(), &reporter);
         let consts = merge_trees(src_consts, env.systems(), &reporter);
         (consts.into_iter())
           .filter(|(k, v)| in_subtrees(k.clone()) && v.comments.iter().any(|c| c.trim() == "test"))
           .collect_vec()
       });
+      reporter.assert_exit();
       eprintln!("Running {} tests", tests.len());
       unwrap_exit!(run_tests(&dir, args.macro_limit, threads, &tests));
       eprintln!("All tests pass");
@@ -252,7 +277,7 @@ pub fn main() -> ExitCode {
           DeclTree::ns("tree::main", [decl_file(&format!("const __repl_input__ := {src}"))]),
           &reporter,
         );
-        let mr = MacroRunner::new(&tree, Some(args.macro_limit), &reporter);
+        let mr = MacroRunner::new(&tree, env.proc_macros(), Some(args.macro_limit), &reporter);
         let proj_consts = mr.run_macros(tree, &reporter).all_consts();
         let consts = merge_trees(proj_consts, env.systems(), &reporter);
         let ctx = nort_gen(location.clone());
@@ -4,8 +4,6 @@
 use std::borrow::Borrow;
 use std::path::PathBuf;
 
-use intern_all::i;
-
 use super::macro_runner::MacroRunner;
 use super::merge_trees::merge_trees;
 use super::process::Process;
@@ -17,8 +15,10 @@ use crate::interpreter::context::RunEnv;
 use crate::interpreter::handler::HandlerTable;
 use crate::location::{CodeGenInfo, CodeOrigin};
 use crate::name::{PathSlice, Sym, VPath};
+use crate::parse::context::LoadState;
 use crate::pipeline::load_project::{load_project, ProjectContext};
 use crate::pipeline::project::ProjectTree;
+use crate::rule::proc_macro::ProcMacro;
 use crate::sym;
 use crate::utils::combine::Combine;
 use crate::utils::sequence::Sequence;
@@ -67,6 +67,11 @@ impl<'a> Loader<'a> {
     (self.systems.iter()).fold(HandlerTable::new(), |t, sys| t.link(&sys.handlers))
   }
 
+  /// Collect the procedural macros of all systems
+  pub fn proc_macros(&self) -> Vec<ProcMacro> {
+    self.systems().flat_map(|sys| sys.proc_macros.iter().cloned()).collect()
+  }
+
   /// Compile the environment from the set of systems and return it directly.
   /// See [#load_dir]
   pub fn project_ctx<'b>(&self, reporter: &'b Reporter) -> ProjectContext<'_, 'b> {
@@ -79,6 +84,7 @@ impl<'a> Loader<'a> {
       }),
       preludes: Sequence::new(|| self.systems().flat_map(|sys| &sys.prelude)),
       reporter,
+      load_state: LoadState::default(),
     }
   }
 
@@ -115,7 +121,8 @@ impl<'a> Loader<'a> {
   pub fn load_project(&self, root: DeclTree, reporter: &Reporter) -> ProjectTree {
     let mut orc_files: Vec<VPath> = Vec::new();
     find_all_orc_files([].borrow(), &mut orc_files, &root);
-    let entrypoints = (orc_files.into_iter()).map(|p| p.name_with_suffix(i!(str: "tree")).to_sym());
+    let entrypoints = (orc_files.into_iter())
+      .map(|p| p.into_name().expect("The root of the file system is a directory").to_sym());
     let tgt_loc = CodeOrigin::Gen(CodeGenInfo::no_details(sym!(facade::entrypoint)));
     let constants = self.constants().unwrap_mod();
     let targets = entrypoints.into_iter().map(|s| (s, tgt_loc.clone()));
@@ -152,7 +159,7 @@ impl<'a> Loader<'a> {
     macro_limit: Option<usize>,
     reporter: &Reporter,
   ) -> Process<'a> {
-    let mr = MacroRunner::new(&tree, macro_limit, reporter);
+    let mr = MacroRunner::new(&tree, self.proc_macros(), macro_limit, reporter);
     let pm_tree = mr.run_macros(tree, reporter);
     let consts = merge_trees(pm_tree.all_consts(), self.systems(), reporter);
     if check_refs {
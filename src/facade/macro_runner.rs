@@ -2,13 +2,36 @@
 //! loaded by the [super::loader::Loader]
 
 use std::iter;
+use std::slice;
+
+use hashbrown::HashMap;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
 use crate::error::{ErrorPosition, ProjectError, ProjectErrorObj, ProjectResult, Reporter};
 use crate::location::CodeOrigin;
-use crate::parse::parsed;
+use crate::name::{Sym, VPath};
+use crate::parse::lexer::print_prio;
+use crate::parse::parsed::{self, PType};
 use crate::pipeline::project::{ItemKind, ProjItem, ProjectTree};
-use crate::rule::repository::Repo;
-use crate::tree::TreeTransforms;
+use crate::rule::proc_macro::ProcMacro;
+use crate::rule::repository::{FiredRule, LongStep, Repo, RuleOrigin, RuleTrace, TokenRole};
+use crate::sym;
+use crate::tree::{ModMember, ModMemberRef, TreeTransforms};
+
+/// Number of rules listed in a [MacroTimeout] error
+const TIMEOUT_HISTORY: usize = 8;
+
+/// Stack size of the threads expanding constants. Substitution is recursive in
+/// the depth of the expression, so the 2 MiB default of rayon isn't enough.
+const EXPANSION_STACK: usize = 16 << 20;
+
+/// Threads that expand constants, shared by all runners. If they can't be
+/// spawned, constants are expanded on the calling thread instead.
+static EXPANSION_POOL: Lazy<Option<ThreadPool>> =
+  Lazy::new(|| ThreadPoolBuilder::new().stack_size(EXPANSION_STACK).build().ok());
 
 /// Encapsulates the macro repository and the constant list, and allows querying
 /// for macro execution results
@@ -19,51 +42,111 @@ pub struct MacroRunner {
   pub timeout: Option<usize>,
 }
 impl MacroRunner {
-  /// Initialize a macro runner
-  pub fn new(tree: &ProjectTree, timeout: Option<usize>, reporter: &Reporter) -> Self {
+  /// Initialize a macro runner with the rules in the tree and the procedural
+  /// macros of the systems
+  pub fn new(
+    tree: &ProjectTree,
+    procs: Vec<ProcMacro>,
+    timeout: Option<usize>,
+    reporter: &Reporter,
+  ) -> Self {
     let rules = tree.all_rules();
-    let repo = Repo::new(rules, reporter);
+    let repo = Repo::new(rules, procs, reporter);
     Self { repo, timeout }
   }
 
-  /// Process the macros in an expression.
+  /// Process the macros in an expression. Error tokens left in the output are
+  /// raised as errors.
   pub fn process_expr(&self, expr: parsed::Expr) -> ProjectResult<parsed::Expr> {
+    let output = self.expand(expr)?;
+    let mut errors = Vec::new();
+    lift_errors(slice::from_ref(&output), &mut errors);
+    match errors.len() {
+      0 => Ok(output),
+      1 => Err(errors.pop().expect("Length checked").pack()),
+      _ => Err(MacroErrors(errors.into_iter().map(|e| e.pack()).collect()).pack()),
+    }
+  }
+
+  fn expand(&self, expr: parsed::Expr) -> ProjectResult<parsed::Expr> {
     match self.timeout {
       None => Ok((self.repo.pass(&expr)).unwrap_or_else(|| expr.clone())),
       Some(limit) => {
-        let (o, leftover_gas) = self.repo.long_step(&expr, limit + 1);
-        if 0 < leftover_gas {
+        let LongStep { expr: o, leftover, history, cycle, .. } =
+          self.repo.long_step(&expr, limit + 1, TIMEOUT_HISTORY);
+        if let Some(steps) = cycle {
+          return Err(MacroCycle { location: expr.range.origin(), steps }.pack());
+        }
+        if 0 < leftover {
           return Ok(o);
         }
-        Err(MacroTimeout { location: expr.range.origin(), limit }.pack())
+        Err(MacroTimeout { location: expr.range.origin(), limit, history }.pack())
       },
     }
   }
 
-  /// Run all macros in the project.
+  /// Run all macros in the project. The constants are processed in parallel
+  /// and errors are reported in the order of their source locations.
   pub fn run_macros(&self, tree: ProjectTree, reporter: &Reporter) -> ProjectTree {
+    let mut consts = Vec::new();
+    tree.0.search_all((), |path, mem, ()| {
+      if let ModMemberRef::Mod(m) = mem {
+        for (name, ent) in m.entries.iter() {
+          if let ModMember::Item(ProjItem { kind: ItemKind::Const(c) }) = &ent.member {
+            if !ent.x.expand {
+              continue;
+            }
+            let name = VPath::new(path.unreverse()).name_with_prefix(name.clone()).to_sym();
+            consts.push((name, c.clone()));
+          }
+        }
+      }
+    });
+    consts.sort_by_cached_key(|(name, c)| {
+      (c.range.path().to_string(), c.range.range().start, name.to_string())
+    });
+    let process = |(name, c): (Sym, parsed::Expr)| (name, (c.range.clone(), self.process_expr(c)));
+    let results = match &*EXPANSION_POOL {
+      Some(pool) => pool.install(|| consts.into_par_iter().map(process).collect::<Vec<_>>()),
+      None => consts.into_iter().map(process).collect(),
+    };
+    let mut processed = HashMap::new();
+    for (name, (range, result)) in results {
+      let stand_in = |_| parsed::Clause::Name(sym!(__macro_error__)).into_expr(range);
+      processed.insert(name, reporter.fallback(result, stand_in));
+    }
     ProjectTree(tree.0.map_data(
-      |_, item| match &item.kind {
-        ItemKind::Const(c) => match self.process_expr(c.clone()) {
-          Ok(expr) => ProjItem { kind: ItemKind::Const(expr) },
-          Err(e) => {
-            reporter.report(e);
-            item
-          },
-        },
-        _ => item,
+      |path, item| {
+        let name = Sym::new(path.unreverse()).expect("Items are always named");
+        match processed.remove(&name) {
+          Some(expr) => ProjItem { kind: ItemKind::Const(expr) },
+          None => item,
+        }
       },
       |_, x| x,
       |_, x| x,
     ))
   }
 
+  /// Expand the macros in an expression and collect the source tokens that
+  /// were given roles by the patterns, eg. so that editors can highlight
+  /// keywords and binders
+  pub fn roles(&self, expr: &parsed::Expr) -> Vec<TokenRole> {
+    let limit = self.timeout.map_or(usize::MAX, |t| t + 1);
+    self.repo.long_step(expr, limit, 0).roles
+  }
+
   /// Obtain an iterator that steps through the preprocessing of a constant
-  /// for debugging macros
-  pub fn step(&self, mut expr: parsed::Expr) -> impl Iterator<Item = parsed::Expr> + '_ {
+  /// for debugging macros. Every step is accompanied by an account of the
+  /// rule that fired.
+  pub fn step(
+    &self,
+    mut expr: parsed::Expr,
+  ) -> impl Iterator<Item = (parsed::Expr, RuleTrace)> + '_ {
     iter::from_fn(move || {
-      expr = self.repo.step(&expr)?;
-      Some(expr.clone())
+      let (out, trace) = self.repo.step_traced(&expr)?;
+      expr = out;
+      Some((expr.clone(), trace))
     })
   }
 }
@@ -73,6 +156,8 @@ impl MacroRunner {
 pub struct MacroTimeout {
   location: CodeOrigin,
   limit: usize,
+  /// The last rules that fired, oldest first
+  history: Vec<RuleOrigin>,
 }
 impl ProjectError for MacroTimeout {
   const DESCRIPTION: &'static str = "Macro execution has not halted";
@@ -82,7 +167,106 @@ impl ProjectError for MacroTimeout {
     format!("Macro processing took more than {limit} steps")
   }
 
-  fn one_position(&self) -> CodeOrigin { self.location.clone() }
+  fn positions(&self) -> impl IntoIterator<Item = ErrorPosition> + '_ {
+    let last_rules = self.history.iter().rev().enumerate().map(|(i, rule)| ErrorPosition {
+      origin: rule.location.origin(),
+      message: Some(match i {
+        0 => format!("Last rule to fire, with priority {}", print_prio(rule.prio)),
+        i => format!("Rule #{} from the end, with priority {}", i + 1, print_prio(rule.prio)),
+      }),
+    });
+    iter::once(ErrorPosition::from(self.location.clone())).chain(last_rules)
+  }
+}
+
+/// Error raised when macro execution returns to a state it was already in
+#[derive(Debug)]
+pub struct MacroCycle {
+  location: CodeOrigin,
+  /// The steps that lead from the repeated state back to itself
+  steps: Vec<FiredRule>,
+}
+impl ProjectError for MacroCycle {
+  const DESCRIPTION: &'static str = "Macro execution is stuck in a loop";
+
+  fn message(&self) -> String {
+    let rules = self.steps.iter().map(|s| s.rule.location.to_string()).join(" -> ");
+    format!("The expression repeats every {} steps, rewritten by {rules}", self.steps.len())
+  }
+
+  fn positions(&self) -> impl IntoIterator<Item = ErrorPosition> + '_ {
+    let steps = self.steps.iter().enumerate().map(|(i, FiredRule { rule, matched })| {
+      let (step, location, prio) = (i + 1, &rule.location, print_prio(rule.prio));
+      let message = format!("Step {step}: rewritten by the rule at {location} ({prio})");
+      ErrorPosition { origin: matched.origin(), message: Some(message) }
+    });
+    iter::once(ErrorPosition::from(self.location.clone())).chain(steps)
+  }
+}
+
+/// Find the error tokens in a sequence and its subexpressions. The token is
+/// followed either by a message, or by a group of a message and the tokens
+/// that caused the error.
+fn lift_errors(seq: &[parsed::Expr], out: &mut Vec<MacroError>) {
+  for (i, expr) in seq.iter().enumerate() {
+    match &expr.value {
+      parsed::Clause::Name(n) if *n == sym!(std::macro::error) =>
+        out.push(MacroError::new(expr, seq.get(i + 1))),
+      parsed::Clause::S(_, body) => lift_errors(body, out),
+      parsed::Clause::Lambda(arg, body) => {
+        lift_errors(arg, out);
+        lift_errors(body, out)
+      },
+      _ => (),
+    }
+  }
+}
+
+/// Served by atoms that can be the message of a [MacroError], such as strings
+#[derive(Clone, Debug)]
+pub struct ErrorMessage(pub String);
+
+/// Error emitted by a macro with the `std::macro::error` token
+#[derive(Debug)]
+pub struct MacroError {
+  /// The message supplied by the macro
+  pub message: String,
+  /// The offending tokens, or the error token if none were supplied
+  pub locations: Vec<CodeOrigin>,
+}
+impl MacroError {
+  fn new(token: &parsed::Expr, details: Option<&parsed::Expr>) -> Self {
+    let string = |e: &parsed::Expr| match &e.value {
+      parsed::Clause::Atom(a) => a.run().request::<ErrorMessage>().map(|m| m.0),
+      _ => None,
+    };
+    let (message, culprits) = match details {
+      None => ("No details were given".to_string(), &[][..]),
+      Some(details) => {
+        let group = match &details.value {
+          parsed::Clause::S(PType::Par, body) => body.split_first(),
+          _ => None,
+        };
+        match (string(details), group.and_then(|(head, tail)| Some((string(head)?, tail)))) {
+          (Some(message), _) => (message, &[][..]),
+          (None, Some((message, tail))) => (message, tail),
+          (None, None) => (details.to_string(), &[][..]),
+        }
+      },
+    };
+    let mut locations = culprits.iter().map(|e| e.range.origin()).collect_vec();
+    if locations.is_empty() {
+      locations.push(token.range.origin())
+    }
+    Self { message, locations }
+  }
+}
+impl ProjectError for MacroError {
+  const DESCRIPTION: &'static str = "A macro reported an error";
+  fn message(&self) -> String { self.message.clone() }
+  fn positions(&self) -> impl IntoIterator<Item = ErrorPosition> + '_ {
+    self.locations.iter().cloned().map(ErrorPosition::from)
+  }
 }
 
 struct MacroErrors(Vec<ProjectErrorObj>);
@@ -100,3 +284,33 @@ impl ProjectError for MacroErrors {
     })
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::error::Reporter;
+  use crate::facade::loader::Loader;
+  use crate::libs::std::std_system::StdConfig;
+  use crate::location::CodeOrigin;
+  use crate::sym;
+  use crate::virt_fs::{decl_file, DeclTree};
+
+  #[test]
+  fn error_token() {
+    let src = "import std::macro\n\
+      macro forbid $x =0x1p100=> (macro::error (\"forbidden\" $x))\n\
+      const main := forbid culprit";
+    let env = Loader::new().add_system(StdConfig { impure: true });
+    let reporter = Reporter::new();
+    let root = DeclTree::ns("tree::main", [decl_file(src)]);
+    let tree = env.load_project_main([sym!(tree::main::main)], root, &reporter);
+    reporter.assert();
+    env.proc(tree, false, Some(1000), &reporter);
+    let errors = reporter.into_errors().expect("the macro raised an error");
+    let [error] = &errors[..] else { panic!("Expected one error, got {}", errors.len()) };
+    assert_eq!(error.message(), "forbidden");
+    let positions = error.positions().collect::<Vec<_>>();
+    let [position] = &positions[..] else { panic!("Expected one position") };
+    let CodeOrigin::Source(range) = &position.origin else { panic!("Not in the source") };
+    assert_eq!(&src[range.range()], "culprit");
+  }
+}
@@ -6,13 +6,13 @@ use std::sync::Arc;
 use hashbrown::HashMap;
 
 use super::system::System;
-use crate::error::Reporter;
+use crate::error::{ProjectError, Reporter};
 use crate::foreign::inert::Inert;
 use crate::foreign::to_clause::ToClause;
 use crate::intermediate::ast_to_ir::ast_to_ir;
 use crate::intermediate::ir_to_nort::ir_to_nort;
 use crate::interpreter::nort;
-use crate::location::{CodeGenInfo, CodeLocation};
+use crate::location::{CodeGenInfo, CodeLocation, CodeOrigin};
 use crate::name::{NameLike, Sym};
 use crate::pipeline::project::ConstReport;
 use crate::sym;
@@ -62,14 +62,39 @@ pub fn merge_trees<'a: 'b, 'b>(
     let const_module = system.constants.unwrap_mod_ref();
     const_module.search_all((), |stack, node, ()| {
       let c = unwrap_or!(node => ModMemberRef::Item; return);
-      let location = CodeLocation::new_gen(CodeGenInfo::details(
+      let name = Sym::new(stack.unreverse()).expect("root item is forbidden");
+      let origin = CodeOrigin::Gen(CodeGenInfo::details(
         sym!(facade::merge_tree),
         format!("system.name={}", system.name),
       ));
+      // constants such as type tags identify themselves by their module
+      let Ok(module) = Sym::new(name.split_last().1[..].iter()) else {
+        let system = system.name.to_string();
+        return reporter.report(GlobalSystemConstant { system, name, origin }.pack());
+      };
+      let location = CodeLocation { origin, module };
       let value = c.clone().gen_nort(stack.clone(), location.clone());
       let crep = NortConst { value, comments: vec![], location };
-      out.insert(Sym::new(stack.unreverse()).expect("root item is forbidden"), crep);
+      out.insert(name, crep);
     });
   }
   out
 }
+
+/// A system defined a constant outside of any module
+#[derive(Debug)]
+pub struct GlobalSystemConstant {
+  /// Name of the system
+  pub system: String,
+  /// Name of the constant
+  pub name: Sym,
+  /// Location of the constant
+  pub origin: CodeOrigin,
+}
+impl ProjectError for GlobalSystemConstant {
+  const DESCRIPTION: &'static str = "Systems can only define constants inside modules";
+  fn message(&self) -> String {
+    format!("System {} defines the constant {} at the root", self.system, self.name)
+  }
+  fn one_position(&self) -> CodeOrigin { self.origin.clone() }
+}
@@ -8,6 +8,7 @@ use crate::name::VName;
 use crate::parse::lex_plugin::LexerPlugin;
 use crate::parse::parse_plugin::ParseLinePlugin;
 use crate::pipeline::load_project::Prelude;
+use crate::rule::proc_macro::ProcMacro;
 use crate::virt_fs::DeclTree;
 
 /// A description of every point where an external library can hook into Orchid.
@@ -32,6 +33,9 @@ pub struct System<'a> {
   /// Parser that processes custom line types into their representation in the
   /// module tree
   pub line_parsers: Vec<Box<dyn ParseLinePlugin>>,
+  /// Substitution rules implemented in Rust, scheduled by priority alongside
+  /// the rules in the source code
+  pub proc_macros: Vec<ProcMacro>,
 }
 impl<'a> System<'a> {
   /// Intern the name of the system so that it can be used as an Orchid
@@ -14,6 +14,11 @@ use crate::location::{CodeGenInfo, CodeLocation};
 use crate::name::Sym;
 use crate::sym;
 
+/// Names that stand in for code that already raised an error
+fn is_stand_in(symbol: &Sym) -> bool {
+  *symbol == sym!(__syntax_error__) || *symbol == sym!(__macro_error__)
+}
+
 /// Start with a symbol
 pub fn unbound_refs_sym<E: SubError>(
   symbol: Sym,
@@ -22,7 +27,7 @@ pub fn unbound_refs_sym<E: SubError>(
   load: &mut impl FnMut(Sym, CodeLocation) -> Result<Expr, E>,
   reporter: &Reporter,
 ) {
-  if visited.insert(symbol.clone()) {
+  if !is_stand_in(&symbol) && visited.insert(symbol.clone()) {
     match load(symbol.clone(), location.clone()) {
       Err(error) => reporter.report(MissingSymbol { symbol, location, error }.pack()),
       Ok(expr) => unbound_refs_expr(expr, visited, load, reporter),
@@ -196,6 +196,8 @@ impl AtomGenerator {
   }
   /// Generate an atom
   pub fn run(&self) -> Atom { self.0() }
+  /// Address of the factory, shared by all clones of this generator
+  pub(crate) fn identity(&self) -> usize { Arc::as_ptr(&self.0) as *const () as usize }
 }
 impl fmt::Debug for AtomGenerator {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:?}", self.run()) }
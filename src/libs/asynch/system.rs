@@ -205,6 +205,7 @@ impl<'a> IntoSystem<'a> for AsynchSystem<'a> {
       code: code(),
       prelude: Vec::new(),
       handlers: handler_table,
+      proc_macros: vec![],
     }
   }
 }
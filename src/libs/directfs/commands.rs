@@ -186,6 +186,7 @@ impl IntoSystem<'static> for DirectFS {
       .combine(os_string_lib())
       .expect("os_string library and directfs conflict"),
       handlers,
+      proc_macros: vec![],
     }
   }
 }
@@ -128,6 +128,7 @@ impl<'a, ST: IntoIterator<Item = (&'a str, Stream)>> IntoSystem<'static> for IOS
       }],
       lexer_plugins: vec![],
       line_parsers: vec![],
+      proc_macros: vec![],
     }
   }
 }
@@ -339,6 +339,7 @@ impl IntoSystem<'static> for SeqScheduler {
         xfn_ent("is_taken_e", [is_taken_e]),
         xfn_ent("take_and_drop", [take_and_drop]),
       ])]),
+      proc_macros: vec![],
     }
   }
 }
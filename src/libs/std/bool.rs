@@ -1,13 +1,15 @@
 use super::number::Numeric;
+use super::protocol::{gen_resolv, Tag};
 use super::string::OrcString;
+use crate::foreign::atom::Atomic;
 use crate::foreign::error::{AssertionError, RTResult};
-use crate::foreign::inert::Inert;
+use crate::foreign::inert::{Inert, InertPayload};
 use crate::foreign::try_from_expr::WithLoc;
 use crate::gen::tpl;
 use crate::gen::traits::{Gen, GenClause};
 use crate::gen::tree::{atom_ent, xfn_ent, ConstTree};
 use crate::interpreter::gen_nort::nort_gen;
-use crate::interpreter::nort::Expr;
+use crate::interpreter::nort::{Clause, Expr};
 
 const fn left() -> impl GenClause { tpl::L("l", tpl::L("_", tpl::P("l"))) }
 const fn right() -> impl GenClause { tpl::L("_", tpl::L("r", tpl::P("r"))) }
@@ -26,16 +28,39 @@ pub fn if_then_else(WithLoc(loc, b): WithLoc<Inert<bool>>) -> Expr {
 /// - both are string,
 /// - both are bool,
 /// - both are either uint or num
-pub fn equals(WithLoc(loc, a): WithLoc<Expr>, b: Expr) -> RTResult<Inert<bool>> {
-  Ok(Inert(if let Ok(l) = a.clone().downcast::<Inert<OrcString>>() {
+///
+/// Values with the same type tag are compared by the `std::bool::equality`
+/// protocol, values with different tags are never equal. Other atoms are
+/// compared with [crate::foreign::atom::Atomic::parser_eq].
+pub fn equals(WithLoc(loc, a): WithLoc<Expr>, b: Expr) -> RTResult<Expr> {
+  let eq = if let Ok(l) = a.clone().downcast::<Inert<OrcString>>() {
     b.downcast::<Inert<OrcString>>().is_ok_and(|r| *l == *r)
   } else if let Ok(l) = a.clone().downcast::<Inert<bool>>() {
     b.downcast::<Inert<bool>>().is_ok_and(|r| *l == *r)
   } else if let Some(l) = a.clause.request::<Numeric>() {
     b.clause.request::<Numeric>().is_some_and(|r| l.as_float() == r.as_float())
+  } else if let Some(l) = a.clause.request::<Tag>() {
+    match b.clause.request::<Tag>() {
+      Some(r) if l.strict_eq(&r) => {
+        let call =
+          tpl::A(tpl::a2(gen_resolv("std::bool::equality"), tpl::Slot, tpl::Slot), tpl::Slot);
+        return Ok(call.template(nort_gen(loc), [a.clone(), a, b]));
+      },
+      _ => false,
+    }
+  } else if a.clause.is_same(&b.clause) {
+    true
   } else {
-    AssertionError::fail(loc, "string, bool or numeric", format!("{a}"))?
-  }))
+    let atoms_eq = a.clause.inspect(|l| {
+      b.clause.inspect(|r| match (l, r) {
+        (Clause::Atom(l), Clause::Atom(r)) => Some(l.0.parser_eq(&*r.0)),
+        _ => None,
+      })
+    });
+    atoms_eq
+      .ok_or_else(|| AssertionError::ext(loc.clone(), "a comparable value", format!("{a}")))?
+  };
+  Ok(Inert(eq).atom_expr(loc))
 }
 
 pub fn bool_lib() -> ConstTree {
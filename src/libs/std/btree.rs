@@ -0,0 +1,349 @@
+//! `std::btree` Ordered maps and sets keyed by a natural ordering over
+//! numbers, strings, booleans and tuples of these.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+
+use super::number::Numeric;
+use super::protocol::Tag;
+use super::reflect::refer;
+use super::string::OrcString;
+use super::tuple::Tuple;
+use crate::foreign::atom::Atomic;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::fn_bridge::Thunk;
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::to_clause::{list, ToClause};
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tpl;
+use crate::gen::traits::Gen;
+use crate::gen::tree::{atom_ent, xfn_ent, ConstTree};
+use crate::interpreter::gen_nort::nort_gen;
+use crate::interpreter::nort::{Clause, Expr};
+use crate::location::{CodeGenInfo, CodeLocation};
+use crate::sym;
+use crate::utils::ddispatch::Request;
+
+/// A fully normalized value with a natural total order. Values of different
+/// kinds are ordered by kind in the order of the variants.
+#[derive(Clone)]
+pub enum OrdKey {
+  /// `false` precedes `true`
+  Bool(bool),
+  /// Ints and floats are compared by their exact value
+  Num(Numeric),
+  /// Strings are compared lexicographically by code point
+  Str(OrcString),
+  /// Tuples are compared lexicographically
+  Tuple(Arc<Vec<OrdKey>>),
+}
+impl OrdKey {
+  fn rank(&self) -> u8 {
+    match self {
+      Self::Bool(_) => 0,
+      Self::Num(_) => 1,
+      Self::Str(_) => 2,
+      Self::Tuple(_) => 3,
+    }
+  }
+}
+/// Compare an int with a float exactly. Casting the int would round it above
+/// 2^53, and then distinct ints could equal the same float.
+fn cmp_uint_float(uint: usize, float: f64) -> Ordering {
+  if float < 0.0 {
+    return Ordering::Greater;
+  }
+  if 2f64.powi(usize::BITS as i32) <= float {
+    return Ordering::Less;
+  }
+  let whole = float.trunc();
+  // exact, because the float is a whole number within the range of usize
+  match uint.cmp(&(whole as usize)) {
+    Ordering::Equal if whole < float => Ordering::Less,
+    ord => ord,
+  }
+}
+
+impl Ord for OrdKey {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self, other) {
+      (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+      (Self::Num(Numeric::Uint(a)), Self::Num(Numeric::Uint(b))) => a.cmp(b),
+      (Self::Num(Numeric::Uint(a)), Self::Num(Numeric::Float(b))) => cmp_uint_float(*a, **b),
+      (Self::Num(Numeric::Float(a)), Self::Num(Numeric::Uint(b))) =>
+        cmp_uint_float(*b, **a).reverse(),
+      (Self::Num(Numeric::Float(a)), Self::Num(Numeric::Float(b))) => a.cmp(b),
+      (Self::Str(a), Self::Str(b)) => a.as_str().cmp(b.as_str()),
+      (Self::Tuple(a), Self::Tuple(b)) => a.iter().cmp(b.iter()),
+      (a, b) => a.rank().cmp(&b.rank()),
+    }
+  }
+}
+impl PartialOrd for OrdKey {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl PartialEq for OrdKey {
+  fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+impl Eq for OrdKey {}
+impl fmt::Debug for OrdKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Bool(b) => write!(f, "{b}"),
+      Self::Num(n) => write!(f, "{}", n.as_float()),
+      Self::Str(s) => write!(f, "{:?}", s.as_str()),
+      Self::Tuple(t) => write!(f, "t[{}]", t.iter().map(|k| format!("{k:?}")).join(", ")),
+    }
+  }
+}
+impl InertPayload for OrdKey {
+  const TYPE_STR: &'static str = "btree key";
+  fn strict_eq(&self, other: &Self) -> bool { self == other }
+}
+impl ToClause for OrdKey {
+  fn to_clause(self, location: CodeLocation) -> Clause {
+    match self {
+      Self::Bool(b) => Inert(b).atom_cls(),
+      Self::Num(n) => n.to_clause(location),
+      Self::Str(s) => Inert(s).atom_cls(),
+      Self::Tuple(t) => {
+        let items = t.iter().map(|k| k.clone().to_expr(location.clone())).collect();
+        Inert(Tuple(Arc::new(items))).atom_cls()
+      },
+    }
+  }
+}
+
+/// Convert a value to a key. Tuples are passed to `std::btree::key::of_tuple`
+/// because their elements may not have been normalized yet.
+fn to_key(WithLoc(loc, value): WithLoc<Expr>) -> RTResult<Expr> {
+  if let Ok(key) = value.clone().downcast::<Inert<OrdKey>>() {
+    return Ok(key.atom_expr(loc));
+  }
+  if value.clone().downcast::<Inert<Tuple>>().is_ok() {
+    let of_tuple = tpl::A(tpl::C("std::btree::key::of_tuple"), tpl::Slot);
+    return Ok(of_tuple.template(nort_gen(loc), [value]));
+  }
+  let key = if let Ok(b) = value.clone().downcast::<Inert<bool>>() {
+    OrdKey::Bool(b.0)
+  } else if let Some(n) = value.clause.request::<Numeric>() {
+    OrdKey::Num(n)
+  } else if let Ok(s) = value.clone().downcast::<Inert<OrcString>>() {
+    OrdKey::Str(s.0)
+  } else {
+    return AssertionError::fail(loc, "number, string, bool or tuple", format!("{value}"));
+  };
+  Ok(Inert(key).atom_expr(loc))
+}
+
+fn push_key(
+  WithLoc(loc, Inert(tup)): WithLoc<Inert<OrdKey>>,
+  Inert(item): Inert<OrdKey>,
+) -> RTResult<Inert<OrdKey>> {
+  match tup {
+    OrdKey::Tuple(items) => {
+      let mut items = Arc::unwrap_or_clone(items);
+      items.push(item);
+      Ok(Inert(OrdKey::Tuple(Arc::new(items))))
+    },
+    key => AssertionError::fail(loc, "tuple key", format!("{key:?}")),
+  }
+}
+
+static MAP_TAG: Lazy<Tag> = Lazy::new(|| {
+  let location = CodeLocation::new_gen(CodeGenInfo::no_details(sym!(std::btree::map)));
+  Tag::new(sym!(std::btree::map), [(
+    sym!(std::string::conversion),
+    refer("std::btree::map::to_string_impl").into_expr(location),
+  )])
+});
+
+static SET_TAG: Lazy<Tag> = Lazy::new(|| {
+  let location = CodeLocation::new_gen(CodeGenInfo::no_details(sym!(std::btree::set)));
+  Tag::new(sym!(std::btree::set), [(
+    sym!(std::string::conversion),
+    refer("std::btree::set::to_string_impl").into_expr(location),
+  )])
+});
+
+/// A persistent map of Orchid values ordered by [OrdKey]
+#[derive(Clone)]
+pub struct OrdMap(pub im::OrdMap<OrdKey, Expr>);
+impl InertPayload for OrdMap {
+  const TYPE_STR: &'static str = "btree map";
+  fn respond(&self, mut request: Request) { request.serve_with(|| MAP_TAG.clone()) }
+}
+impl fmt::Debug for OrdMap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "OrdMap")?;
+    f.debug_map().entries(self.0.iter().map(|(k, v)| (k, &v.clause))).finish()
+  }
+}
+
+/// A persistent set of [OrdKey]
+#[derive(Clone)]
+pub struct OrdSet(pub im::OrdSet<OrdKey>);
+impl InertPayload for OrdSet {
+  const TYPE_STR: &'static str = "btree set";
+  fn respond(&self, mut request: Request) { request.serve_with(|| SET_TAG.clone()) }
+}
+impl fmt::Debug for OrdSet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "OrdSet")?;
+    f.debug_set().entries(self.0.iter()).finish()
+  }
+}
+
+/// Entries with a key that is at least `lo` and less than `hi`
+fn map_range(map: im::OrdMap<OrdKey, Expr>, lo: &OrdKey, hi: &OrdKey) -> im::OrdMap<OrdKey, Expr> {
+  let (_, lo_val, upper) = map.split_lookup(lo);
+  let (mut range, _) = upper.split(hi);
+  if let Some(val) = lo_val.filter(|_| lo < hi) {
+    range.insert(lo.clone(), val);
+  }
+  range
+}
+
+/// Keys that are at least `lo` and less than `hi`
+fn set_range(set: im::OrdSet<OrdKey>, lo: &OrdKey, hi: &OrdKey) -> im::OrdSet<OrdKey> {
+  let (_, found, upper) = set.split_member(lo);
+  let (mut range, _) = upper.split(hi);
+  if found && lo < hi {
+    range.insert(lo.clone());
+  }
+  range
+}
+
+fn map_split(
+  Inert(map): Inert<OrdMap>,
+  Inert(key): Inert<OrdKey>,
+) -> (Inert<OrdMap>, Inert<OrdMap>) {
+  let (lower, val, mut upper) = map.0.split_lookup(&key);
+  if let Some(val) = val {
+    upper.insert(key, val);
+  }
+  (Inert(OrdMap(lower)), Inert(OrdMap(upper)))
+}
+
+fn map_lib() -> ConstTree {
+  MAP_TAG.to_tree([
+    atom_ent("empty", [Inert(OrdMap(im::OrdMap::new()))]),
+    xfn_ent("size", [|map: Inert<OrdMap>| Inert(map.0.0.len())]),
+    xfn_ent("insert_key", [|Inert(mut map): Inert<OrdMap>, key: Inert<OrdKey>, value: Thunk| {
+      map.0.insert(key.0, value.0);
+      Inert(map)
+    }]),
+    xfn_ent("get_key", [|map: Inert<OrdMap>, key: Inert<OrdKey>| map.0.0.get(&key.0).cloned()]),
+    xfn_ent("remove_key", [|map: Inert<OrdMap>, key: Inert<OrdKey>| {
+      Inert(OrdMap(map.0.0.without(&key.0)))
+    }]),
+    xfn_ent("min", [|map: Inert<OrdMap>| map.0.0.get_min().cloned()]),
+    xfn_ent("max", [|map: Inert<OrdMap>| map.0.0.get_max().cloned()]),
+    xfn_ent("range_keys", [|map: Inert<OrdMap>, lo: Inert<OrdKey>, hi: Inert<OrdKey>| {
+      list(map_range(map.0.0, &lo.0, &hi.0))
+    }]),
+    xfn_ent("split_key", [map_split]),
+    xfn_ent("merge", [|Inert(a): Inert<OrdMap>, Inert(b): Inert<OrdMap>| {
+      Inert(OrdMap(b.0.union(a.0)))
+    }]),
+    xfn_ent("to_list", [|map: Inert<OrdMap>| list(map.0.0)]),
+  ])
+}
+
+fn set_split(
+  Inert(set): Inert<OrdSet>,
+  Inert(key): Inert<OrdKey>,
+) -> (Inert<OrdSet>, Inert<OrdSet>) {
+  let (lower, found, mut upper) = set.0.split_member(&key);
+  if found {
+    upper.insert(key);
+  }
+  (Inert(OrdSet(lower)), Inert(OrdSet(upper)))
+}
+
+fn set_lib() -> ConstTree {
+  SET_TAG.to_tree([
+    atom_ent("empty", [Inert(OrdSet(im::OrdSet::new()))]),
+    xfn_ent("size", [|set: Inert<OrdSet>| Inert(set.0.0.len())]),
+    xfn_ent("insert_key", [|Inert(mut set): Inert<OrdSet>, key: Inert<OrdKey>| {
+      set.0.insert(key.0);
+      Inert(set)
+    }]),
+    xfn_ent("contains_key", [|set: Inert<OrdSet>, key: Inert<OrdKey>| {
+      Inert(set.0.0.contains(&key.0))
+    }]),
+    xfn_ent("remove_key", [|set: Inert<OrdSet>, key: Inert<OrdKey>| {
+      Inert(OrdSet(set.0.0.without(&key.0)))
+    }]),
+    xfn_ent("min", [|set: Inert<OrdSet>| set.0.0.get_min().cloned()]),
+    xfn_ent("max", [|set: Inert<OrdSet>| set.0.0.get_max().cloned()]),
+    xfn_ent("range_keys", [|set: Inert<OrdSet>, lo: Inert<OrdKey>, hi: Inert<OrdKey>| {
+      list(set_range(set.0.0, &lo.0, &hi.0))
+    }]),
+    xfn_ent("split_key", [set_split]),
+    xfn_ent("merge", [|Inert(a): Inert<OrdSet>, Inert(b): Inert<OrdSet>| {
+      Inert(OrdSet(a.0.union(b.0)))
+    }]),
+    xfn_ent("to_list", [|set: Inert<OrdSet>| list(set.0.0)]),
+  ])
+}
+
+pub(super) fn btree_lib() -> ConstTree {
+  ConstTree::ns("std::btree", [ConstTree::tree([
+    ConstTree::tree_ent("key", [
+      xfn_ent("convert", [to_key]),
+      atom_ent("empty_tuple", [Inert(OrdKey::Tuple(Arc::new(Vec::new())))]),
+      xfn_ent("push", [push_key]),
+    ]),
+    ("map", map_lib()),
+    ("set", set_lib()),
+  ])])
+}
+
+#[cfg(test)]
+mod test {
+  use std::cmp::Ordering;
+
+  use ordered_float::NotNan;
+
+  use super::OrdKey;
+  use crate::libs::std::number::Numeric;
+  use crate::libs::std::test_utils::run;
+
+  #[test]
+  fn mixed_number_order() {
+    let uint = |u| OrdKey::Num(Numeric::Uint(u));
+    let float = |f| OrdKey::Num(Numeric::Float(NotNan::new(f).unwrap()));
+    let big = 1usize << 53;
+    // big + 1 rounds to the float of big, but must not equal it
+    assert_eq!(uint(big).cmp(&float(big as f64)), Ordering::Equal);
+    assert_eq!(uint(big + 1).cmp(&float(big as f64)), Ordering::Greater);
+    assert_eq!(float(big as f64).cmp(&uint(big + 1)), Ordering::Less);
+    assert_eq!(uint(2).cmp(&float(2.5)), Ordering::Less);
+    assert_eq!(uint(3).cmp(&float(2.5)), Ordering::Greater);
+    assert_eq!(uint(0).cmp(&float(-0.5)), Ordering::Greater);
+    assert_eq!(uint(usize::MAX).cmp(&float(f64::INFINITY)), Ordering::Less);
+    assert_eq!(OrdKey::Bool(true).cmp(&uint(0)), Ordering::Less);
+  }
+
+  #[test]
+  fn map_and_set_ops() {
+    let run = |expr: &str| run(&format!("import std::btree\nconst main := {expr}"));
+    let m = "(btree::map::new[3 = \"c\", 1 = \"a\", 2.5 = \"b\"])";
+    assert_eq!(run(m).unwrap(), "btree::map[1 = a, 2.5 = b, 3 = c]");
+    let get = |k| run(&format!("option::fallback (btree::map::get {m} {k}) \"none\""));
+    assert_eq!((get("2.5").unwrap(), get("2").unwrap()), ("b".to_string(), "none".to_string()));
+    let removed = run(&format!("btree::map::remove {m} 1"));
+    assert_eq!(removed.unwrap(), "btree::map[2.5 = b, 3 = c]");
+    let range = run(&format!("btree::map::from_list (btree::map::range {m} 2 3)"));
+    assert_eq!(range.unwrap(), "btree::map[2.5 = b]");
+    let s = "(btree::set::new[\"b\", \"a\", \"b\"])";
+    assert_eq!(run(s).unwrap(), "btree::set[a, b]");
+    assert_eq!(run(&format!("btree::set::contains {s} \"a\"")).unwrap(), "true");
+    assert_eq!(run(&format!("btree::set::size (btree::set::insert {s} \"c\")")).unwrap(), "3");
+  }
+}
@@ -0,0 +1,44 @@
+//! Helpers for building the code that line plugins such as `data`, `record`
+//! and `operator` expand to
+
+use std::sync::Arc;
+
+use intern_all::{i, Tok};
+
+use crate::foreign::atom::Atomic;
+use crate::location::SourceRange;
+use crate::name::Sym;
+use crate::parse::parsed::{Clause, Expr, PHClass, PType, Placeholder};
+
+/// Builds expressions that all point to the line they were generated from
+#[derive(Clone)]
+pub(super) struct CodeGen(pub SourceRange);
+impl CodeGen {
+  /// The location of the generated code
+  pub fn range(&self) -> SourceRange { self.0.clone() }
+
+  /// Wrap a clause in an expression
+  pub fn expr(&self, clause: Clause) -> Expr { clause.into_expr(self.range()) }
+
+  /// Reference a name. Relative names are resolved in the generated module.
+  pub fn name(&self, name: Sym) -> Expr { self.expr(Clause::Name(name)) }
+
+  /// Reference a name with a single segment
+  pub fn local(&self, name: Tok<String>) -> Expr {
+    self.name(Sym::new([name]).expect("Not empty"))
+  }
+
+  /// Wrap a sequence in parentheses. Unlike [Clause::into_expr], this keeps the
+  /// parentheses around a single item.
+  pub fn call(&self, items: Vec<Expr>) -> Expr {
+    Expr { value: Clause::S(PType::Par, Arc::new(items)), range: self.range() }
+  }
+
+  /// Embed an atom
+  pub fn atom(&self, atom: impl Atomic + Clone) -> Expr { atom.ast_exp::<Sym>(self.range()) }
+
+  /// Insert a placeholder
+  pub fn placeh(&self, name: &str, class: PHClass) -> Expr {
+    self.expr(Clause::Placeh(Placeholder { name: i(name), class }))
+  }
+}
@@ -0,0 +1,72 @@
+//! `std::compare` Total ordering of values. Numbers, strings and bools are
+//! compared here, every other value is dispatched to the
+//! `std::compare::comparison` protocol.
+
+use std::cmp::Ordering;
+
+use super::number::Numeric;
+use super::protocol::gen_resolv;
+use super::string::OrcString;
+use crate::foreign::atom::Atomic;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tpl;
+use crate::gen::traits::Gen;
+use crate::gen::tree::{atom_ent, xfn_ent, ConstTree};
+use crate::interpreter::gen_nort::nort_gen;
+use crate::interpreter::nort::Expr;
+use crate::utils::ddispatch::Request;
+
+impl InertPayload for Ordering {
+  const TYPE_STR: &'static str = "ordering";
+  fn strict_eq(&self, other: &Self) -> bool { self == other }
+  fn respond(&self, mut request: Request) {
+    request.serve_with(|| {
+      OrcString::from(match self {
+        Ordering::Less => "less",
+        Ordering::Equal => "equal",
+        Ordering::Greater => "greater",
+      })
+    })
+  }
+}
+
+fn expect<T>(val: Option<T>, b: &Expr, expected: &'static str) -> RTResult<T> {
+  val.ok_or_else(|| AssertionError::ext(b.location(), expected, format!("{b}")))
+}
+
+/// Compare two values of the same type. Numbers of different kinds are
+/// compared as floats.
+pub fn compare(WithLoc(loc, a): WithLoc<Expr>, b: Expr) -> RTResult<Expr> {
+  let ord = if let Ok(l) = a.clone().downcast::<Inert<OrcString>>() {
+    let r = expect(b.clone().downcast::<Inert<OrcString>>().ok(), &b, "a string")?;
+    l.0.as_str().cmp(r.0.as_str())
+  } else if let Ok(l) = a.clone().downcast::<Inert<bool>>() {
+    let r = expect(b.clone().downcast::<Inert<bool>>().ok(), &b, "a bool")?;
+    l.0.cmp(&r.0)
+  } else if let Some(l) = a.clause.request::<Numeric>() {
+    match (l, expect(b.clause.request::<Numeric>(), &b, "a number")?) {
+      (Numeric::Uint(l), Numeric::Uint(r)) => l.cmp(&r),
+      (l, r) => l.as_float().cmp(&r.as_float()),
+    }
+  } else {
+    let call =
+      tpl::A(tpl::a2(gen_resolv("std::compare::comparison"), tpl::Slot, tpl::Slot), tpl::Slot);
+    return Ok(call.template(nort_gen(loc), [a.clone(), a, b]));
+  };
+  Ok(Inert(ord).atom_expr(loc))
+}
+
+pub(super) fn compare_lib() -> ConstTree {
+  ConstTree::ns("std::compare", [ConstTree::tree([
+    xfn_ent("compare", [compare]),
+    atom_ent("less", [Inert(Ordering::Less)]),
+    atom_ent("equal", [Inert(Ordering::Equal)]),
+    atom_ent("greater", [Inert(Ordering::Greater)]),
+    xfn_ent("is_less", [|o: Inert<Ordering>| Inert(o.0.is_lt())]),
+    xfn_ent("is_equal", [|o: Inert<Ordering>| Inert(o.0.is_eq())]),
+    xfn_ent("is_greater", [|o: Inert<Ordering>| Inert(o.0.is_gt())]),
+    xfn_ent("reverse", [|o: Inert<Ordering>| Inert(o.0.reverse())]),
+  ])])
+}
@@ -1,5 +1,5 @@
 use std::iter;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
@@ -59,7 +59,7 @@ pub fn defer_to_runtime<T: TryFromExpr + Clone + Send + 'static, R: ToClause + '
   exprs: impl Iterator<Item = Vec<parsed::Expr>>,
   callback: impl DeferredRuntimeCallback<T, R>,
 ) -> parsed::Clause {
-  let argv = exprs.into_iter().map(|v| parsed::Clause::S(PType::Par, Rc::new(v))).collect_vec();
+  let argv = exprs.into_iter().map(|v| parsed::Clause::S(PType::Par, Arc::new(v))).collect_vec();
   let items = iter::once(table_receiver(argv.len(), callback)).chain(argv);
   parsed::Clause::s('(', items, range)
 }
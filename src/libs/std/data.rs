@@ -0,0 +1,179 @@
+//! `std::data` Algebraic data types, declared with a
+//! `data name ( variant field ...; variant field ... )` line.
+//!
+//! The line expands to a type module with a constructor for every variant,
+//! a `variant` function that returns the name of a value's variant, a
+//! `std::string::conversion` impl and a `pmatch` pattern for every variant
+//! that matches the fields positionally.
+
+use std::sync::Arc;
+
+use intern_all::{i, Tok};
+use ordered_float::NotNan;
+
+use super::codegen::CodeGen;
+use super::protocol::{parse_type_body, Impl};
+use super::string::OrcString;
+use crate::error::ProjectResult;
+use crate::foreign::inert::Inert;
+use crate::libs::parse_custom_line::custom_line;
+use crate::location::SourceRange;
+use crate::parse::errors::ParseErrorKind;
+use crate::parse::frag::Frag;
+use crate::parse::lexer::{Entry, Lexeme};
+use crate::parse::parse_plugin::{ParseLinePlugin, ParsePluginReq};
+use crate::parse::parsed::{
+  self, Constant, Member, MemberKind, ModuleBlock, PHClass, PType, Rule, SourceLineKind,
+};
+use crate::sym;
+
+/// A variant of an algebraic data type
+struct Variant {
+  name: Tok<String>,
+  arity: usize,
+}
+
+/// Something other than a name in the variant list of a `data` line
+struct BadVariant(Lexeme);
+impl ParseErrorKind for BadVariant {
+  const DESCRIPTION: &'static str =
+    "Variants must be a name followed by field names, separated by semicolons";
+  fn message(&self) -> String { format!("{} is not a valid variant or field name", self.0) }
+}
+
+/// A variant name that appears more than once in a `data` line
+struct DuplicateVariant(Tok<String>);
+impl ParseErrorKind for DuplicateVariant {
+  const DESCRIPTION: &'static str = "Variant names must be unique within a type";
+  fn message(&self) -> String { format!("{} is declared more than once", self.0) }
+}
+
+/// Parse the semicolon or line break separated list of variants
+fn parse_variants(block: Frag, req: &dyn ParsePluginReq) -> ProjectResult<Vec<Variant>> {
+  let is_sep = |e: &Entry| match &e.lexeme {
+    Lexeme::BR => true,
+    Lexeme::Name(n) => **n == ";",
+    _ => false,
+  };
+  let mut variants: Vec<Variant> = Vec::new();
+  for line in block.data.split(is_sep) {
+    let mut names = line.iter().filter(|e| !e.is_filler()).map(|e| match &e.lexeme {
+      Lexeme::Name(n) => Ok((n.clone(), e)),
+      lexeme => Err(BadVariant(lexeme.clone()).pack(req.range_loc(e.range.clone()))),
+    });
+    let Some(head) = names.next() else { continue };
+    let (name, entry) = head?;
+    if variants.iter().any(|v| v.name == name) {
+      return Err(DuplicateVariant(name).pack(req.range_loc(entry.range.clone())));
+    }
+    let arity = names.collect::<ProjectResult<Vec<_>>>()?.len();
+    variants.push(Variant { name, arity });
+  }
+  Ok(variants)
+}
+
+/// Generate the type module for a data type with the given variants
+fn data_body(
+  variants: &[Variant],
+  req: &dyn ParsePluginReq,
+  range: SourceRange,
+) -> ProjectResult<Vec<parsed::SourceLine>> {
+  let g = CodeGen(range);
+  let string = |s: &Tok<String>| g.atom(Inert(OrcString::from(s.clone())));
+  let show = [sym!(std::data::show), sym!(__type_id__), sym!(unwrap)].map(|n| g.name(n));
+  let show = g.call(show.into());
+  let conversion = Impl { target: sym!(std::string::conversion::__protocol_id__), value: show };
+  let empty = Frag::new(req.frag().fallback, &[]);
+  let mut lines = parse_type_body(empty, req, g.range(), [conversion])?;
+  let variant = g.call(vec![g.name(sym!(std::data::variant)), g.name(sym!(unwrap))]);
+  lines.push(
+    MemberKind::Constant(Constant { name: i!(str: "variant"), value: variant, expand: true })
+      .into_line(true, g.range()),
+  );
+  for Variant { name, arity } in variants {
+    let ctor = vec![
+      g.name(sym!(std::data::constructor)),
+      g.name(sym!(wrap)),
+      string(name),
+      g.atom(Inert(*arity)),
+    ];
+    let ctor = Constant { name: name.clone(), value: g.call(ctor), expand: true };
+    lines.push(MemberKind::Constant(ctor).into_line(true, g.range()));
+    let fields = (0..*arity).map(|n| g.placeh(&format!("f{n}"), PHClass::Scalar));
+    let fields = fields.collect::<Vec<_>>();
+    let pattern = [g.local(name.clone())].into_iter().chain(fields.clone());
+    let items = [g.name(sym!(std::known::=)), string(name)]
+      .into_iter()
+      .chain(fields.into_iter().flat_map(|f| [g.name(sym!(std::known::,)), f]));
+    let tuple = g.expr(parsed::Clause::S(PType::Sqr, Arc::new(items.collect())));
+    let tuple = g.call(vec![g.name(sym!(std::tuple::t)), tuple]);
+    let rule = Rule {
+      pattern: vec![g.name(sym!(std::pmatch::request)), g.call(pattern.collect())],
+      prio: NotNan::new(16f64.powi(230)).expect("Not NaN"),
+      template: vec![
+        g.name(sym!(std::data::variant_pattern)),
+        g.call(vec![g.name(sym!(std::protocol::is)), g.name(sym!(__type_data__))]),
+        g.name(sym!(unwrap)),
+        g.call(vec![g.name(sym!(std::pmatch::request)), tuple]),
+      ],
+      roles: vec![],
+    };
+    lines.push(MemberKind::Rule(rule).into_line(false, g.range()));
+  }
+  Ok(lines)
+}
+
+#[derive(Clone)]
+struct DataParser;
+impl ParseLinePlugin for DataParser {
+  fn parse(&self, req: &dyn ParsePluginReq) -> Option<ProjectResult<Vec<SourceLineKind>>> {
+    custom_line(req.frag(), i!(str: "data"), true, req).map(|res| {
+      let (exported, tail, line_loc) = res?;
+      let (name, tail) = req.pop(tail)?;
+      let name = req.expect_name(name)?;
+      let block = req.expect_block(tail, PType::Par)?;
+      let variants = parse_variants(block, req)?;
+      let body = data_body(&variants, req, line_loc)?;
+      let kind = MemberKind::Module(ModuleBlock { name, body });
+      Ok(vec![SourceLineKind::Member(Member { exported, kind })])
+    })
+  }
+}
+
+/// Collection of all the parser plugins defined here
+pub fn parsers() -> Vec<Box<dyn ParseLinePlugin>> { vec![Box::new(DataParser)] }
+
+#[cfg(test)]
+mod test {
+  use crate::libs::std::test_utils::run;
+
+  const SHAPE: &str = "import std::data\ndata shape (circle r; rect w h; empty)\n";
+
+  #[test]
+  fn constructors() {
+    let variant = "const main := shape::variant (shape::rect 1 2)";
+    assert_eq!(run(&format!("{SHAPE}{variant}")).unwrap(), "rect");
+    let shown = "const main := shape::rect 1 2";
+    assert_eq!(run(&format!("{SHAPE}{shown}")).unwrap(), "shape::rect(1, 2)");
+    let nullary = "const main := shape::empty";
+    assert_eq!(run(&format!("{SHAPE}{nullary}")).unwrap(), "shape::empty");
+  }
+
+  #[test]
+  fn exhaustive_match() {
+    let area = "const area := \\s. match s {\n\
+        shape::circle r => r * r * 3;\n\
+        shape::rect w h => w * h;\n\
+        shape::empty => 0;\n\
+      }\n\
+      const main := area (shape::rect 2 3) + area (shape::circle 1) + area shape::empty";
+    assert_eq!(run(&format!("{SHAPE}{area}")).unwrap(), "9");
+  }
+
+  #[test]
+  fn missing_arm() {
+    let partial = "const main := match shape::rect 1 2 { shape::circle r => r; }";
+    let err = run(&format!("{SHAPE}{partial}")).unwrap_err();
+    assert!(err.contains("no arms match shape::rect(1, 2)"), "{err}");
+  }
+}
@@ -0,0 +1,371 @@
+//! `std::string::format_value` and `std::string::format`, rendering values
+//! according to format specifications similar to Rust's, such as `08.3`, `x`,
+//! `<20` or `?`. The same specifications appear after a colon in the `${}`
+//! blocks of template strings.
+
+use std::iter;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::number::Numeric;
+use super::runtime_error::RuntimeError;
+use super::string::OrcString;
+use super::tuple::Tuple;
+use crate::foreign::atom::Atomic;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tpl;
+use crate::gen::traits::Gen;
+use crate::gen::tree::{xfn_ent, ConstTree};
+use crate::interpreter::gen_nort::nort_gen;
+use crate::interpreter::nort::Expr;
+use crate::location::CodeLocation;
+use crate::parse::errors::ParseErrorKind;
+
+/// Placement of the value within the padded field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+  /// `<`, the default for strings
+  Left,
+  /// `^`, extra fill goes to the right
+  Center,
+  /// `>`, the default for numbers
+  Right,
+}
+
+/// The representation selected by the last character of a spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatKind {
+  /// No type character, the string conversion
+  Display,
+  /// `?`, strings are quoted and escaped
+  Debug,
+  /// `x`
+  LowerHex,
+  /// `X`
+  UpperHex,
+  /// `o`
+  Octal,
+  /// `b`
+  Binary,
+  /// `e`, scientific notation
+  Exp,
+}
+
+/// A parsed format specification
+/// `[[fill]align][+][#][0][width][.precision][type]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatSpec {
+  /// Character used to pad the value to the width
+  pub fill: char,
+  /// Alignment, or the default of the value's type
+  pub align: Option<Align>,
+  /// Print a `+` before non-negative numbers
+  pub sign: bool,
+  /// Print the `0x`, `0o` or `0b` prefix before integers in other radices
+  pub alternate: bool,
+  /// Pad numbers with zeroes between the sign and the digits
+  pub zero: bool,
+  /// Minimum length in graphemes
+  pub width: usize,
+  /// Number of decimals for numbers, maximum length for strings
+  pub precision: Option<usize>,
+  /// Representation
+  pub kind: FormatKind,
+}
+impl Default for FormatSpec {
+  fn default() -> Self {
+    Self {
+      fill: ' ',
+      align: None,
+      sign: false,
+      alternate: false,
+      zero: false,
+      width: 0,
+      precision: None,
+      kind: FormatKind::Display,
+    }
+  }
+}
+impl FormatSpec {
+  /// Parse the part of a placeholder after the colon
+  pub fn parse(spec: &str) -> Result<Self, String> {
+    let align_of = |c| match c {
+      '<' => Some(Align::Left),
+      '^' => Some(Align::Center),
+      '>' => Some(Align::Right),
+      _ => None,
+    };
+    let mut out = Self::default();
+    let mut chars = spec.chars().peekable();
+    let mut lookahead = spec.chars();
+    match (lookahead.next(), lookahead.next().and_then(align_of)) {
+      (Some(fill), Some(align)) => {
+        (out.fill, out.align) = (fill, Some(align));
+        chars.nth(1);
+      },
+      (Some(c), _) if align_of(c).is_some() => {
+        out.align = align_of(c);
+        chars.next();
+      },
+      _ => (),
+    }
+    out.sign = chars.next_if_eq(&'+').is_some();
+    out.alternate = chars.next_if_eq(&'#').is_some();
+    out.zero = chars.next_if_eq(&'0').is_some();
+    let number = |chars: &mut iter::Peekable<std::str::Chars>| {
+      let digits = iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect::<String>();
+      match digits.is_empty() {
+        true => Ok(None),
+        false => digits.parse().map(Some).map_err(|_| format!("{digits} is too large")),
+      }
+    };
+    out.width = number(&mut chars)?.unwrap_or(0);
+    if chars.next_if_eq(&'.').is_some() {
+      out.precision = Some(number(&mut chars)?.ok_or("expected digits after .")?);
+    }
+    out.kind = match chars.next() {
+      None => return Ok(out),
+      Some('?') => FormatKind::Debug,
+      Some('x') => FormatKind::LowerHex,
+      Some('X') => FormatKind::UpperHex,
+      Some('o') => FormatKind::Octal,
+      Some('b') => FormatKind::Binary,
+      Some('e') => FormatKind::Exp,
+      Some(c) => return Err(format!("unknown format type {c:?}")),
+    };
+    match chars.next() {
+      None => Ok(out),
+      Some(c) => Err(format!("unexpected {c:?} after the format type")),
+    }
+  }
+
+  /// Whether this spec can only be applied to numbers
+  pub fn needs_number(&self) -> bool {
+    self.sign || self.alternate || !matches!(self.kind, FormatKind::Display | FormatKind::Debug)
+  }
+
+  fn align(&self, s: String, default: Align) -> String {
+    let missing = self.width.saturating_sub(s.graphemes(true).count());
+    let (before, after) = match self.align.unwrap_or(default) {
+      Align::Left => (0, missing),
+      Align::Center => (missing / 2, missing - missing / 2),
+      Align::Right => (missing, 0),
+    };
+    let fill = |n| self.fill.to_string().repeat(n);
+    format!("{}{s}{}", fill(before), fill(after))
+  }
+
+  /// Render a string. The precision truncates it to a number of graphemes.
+  pub fn render_str(&self, s: &str) -> String {
+    let s = match self.kind {
+      FormatKind::Debug => format!("{s:?}"),
+      _ => s.to_string(),
+    };
+    let s = match self.precision {
+      Some(p) => s.graphemes(true).take(p).collect(),
+      None => s,
+    };
+    self.align(s, Align::Left)
+  }
+
+  /// Render a number. Radix formats only accept whole numbers.
+  pub fn render_num(&self, num: Numeric) -> Result<String, String> {
+    let negative = num.as_f64() < 0.0;
+    let (prefix, digits) = match (self.kind, num) {
+      (FormatKind::Exp, num) => match self.precision {
+        Some(p) => ("", format!("{:.p$e}", num.as_f64().abs())),
+        None => ("", format!("{:e}", num.as_f64().abs())),
+      },
+      (FormatKind::Display | FormatKind::Debug, Numeric::Uint(n)) => match self.precision {
+        Some(p) => ("", format!("{:.p$}", n as f64)),
+        None => ("", n.to_string()),
+      },
+      (FormatKind::Display | FormatKind::Debug, Numeric::Float(f)) => match self.precision {
+        Some(p) => ("", format!("{:.p$}", f.abs())),
+        None => ("", f.abs().to_string()),
+      },
+      (kind, num) => {
+        let n = match num {
+          Numeric::Uint(n) => n,
+          Numeric::Float(f) if f.fract() == 0.0 && f.abs() <= usize::MAX as f64 => f.abs() as usize,
+          Numeric::Float(f) => return Err(format!("{f} is not a whole number")),
+        };
+        match kind {
+          FormatKind::LowerHex => ("0x", format!("{n:x}")),
+          FormatKind::UpperHex => ("0x", format!("{n:X}")),
+          FormatKind::Octal => ("0o", format!("{n:o}")),
+          FormatKind::Binary => ("0b", format!("{n:b}")),
+          _ => unreachable!("decimal formats handled above"),
+        }
+      },
+    };
+    let sign = if negative {
+      "-"
+    } else if self.sign {
+      "+"
+    } else {
+      ""
+    };
+    let head = format!("{sign}{}", if self.alternate { prefix } else { "" });
+    if self.zero {
+      let missing = self.width.saturating_sub(head.len() + digits.len());
+      return Ok(format!("{head}{}{digits}", "0".repeat(missing)));
+    }
+    Ok(self.align(head + &digits, Align::Right))
+  }
+}
+impl InertPayload for FormatSpec {
+  const TYPE_STR: &'static str = "format spec";
+  fn strict_eq(&self, other: &Self) -> bool { self == other }
+}
+
+/// A format specification in a `${}` block could not be parsed
+pub(super) struct BadFormatSpec(pub String);
+impl ParseErrorKind for BadFormatSpec {
+  const DESCRIPTION: &'static str = "Invalid format specification";
+  fn message(&self) -> String { format!("Invalid format specification: {}", self.0) }
+}
+
+/// Render a value according to the spec. Values other than numbers and strings
+/// are converted with `std::string::convert` first.
+fn format_value(spec: Inert<FormatSpec>, WithLoc(loc, value): WithLoc<Expr>) -> RTResult<Expr> {
+  let Inert(spec) = spec;
+  if let Ok(Inert(s)) = value.clone().downcast::<Inert<OrcString>>() {
+    if !spec.needs_number() {
+      return Ok(Inert(OrcString::from(spec.render_str(s.as_str()))).atom_expr(loc));
+    }
+  }
+  if let Some(num) = value.clause.request::<Numeric>() {
+    let text =
+      spec.render_num(num).map_err(|e| AssertionError::ext(loc.clone(), "an integer", e))?;
+    return Ok(Inert(OrcString::from(text)).atom_expr(loc));
+  }
+  if spec.needs_number() {
+    return AssertionError::fail(loc, "a number", format!("{value}"));
+  }
+  let display = FormatSpec { kind: FormatKind::Display, ..spec };
+  let convert = tpl::A(tpl::C("std::string::convert"), tpl::Slot);
+  Ok(
+    tpl::a2(tpl::C("std::string::format_value"), tpl::V(Inert(display)), convert)
+      .template(nort_gen(loc), [value]),
+  )
+}
+
+/// A section of a runtime format string
+enum Segment {
+  Lit(String),
+  Arg(usize, FormatSpec),
+}
+
+/// Split a format string into literal text and `{}`, `{1}`, `{:spec}` or
+/// `{1:spec}` placeholders. Braces are escaped by doubling them.
+fn parse_format(fmt: &str) -> Result<Vec<Segment>, String> {
+  let mut segments = Vec::new();
+  let mut lit = String::new();
+  let mut next_arg = 0;
+  let mut tail = fmt;
+  while let Some(c) = tail.chars().next() {
+    tail = &tail[c.len_utf8()..];
+    match c {
+      '{' | '}' if tail.starts_with(c) => {
+        lit.push(c);
+        tail = &tail[1..];
+      },
+      '}' => return Err("unmatched } in format string".to_string()),
+      '{' => {
+        let (body, rest) = tail.split_once('}').ok_or("unclosed { in format string")?;
+        let (idx, spec) = body.split_once(':').unwrap_or((body, ""));
+        let idx = match idx {
+          "" => next_arg,
+          idx => idx.parse().map_err(|_| format!("{idx:?} is not an argument index"))?,
+        };
+        next_arg = idx + 1;
+        segments.push(Segment::Lit(std::mem::take(&mut lit)));
+        segments.push(Segment::Arg(idx, FormatSpec::parse(spec)?));
+        tail = rest;
+      },
+      c => lit.push(c),
+    }
+  }
+  segments.push(Segment::Lit(lit));
+  Ok(segments)
+}
+
+fn format(fmt: Inert<OrcString>, WithLoc(loc, args): WithLoc<Inert<Tuple>>) -> RTResult<Expr> {
+  let segments =
+    parse_format(fmt.0.as_str()).map_err(|e| RuntimeError::ext(e, "parsing format string"))?;
+  let gen = |loc: &CodeLocation| nort_gen(loc.clone());
+  let mut out = Inert(OrcString::from("")).atom_expr(loc.clone());
+  for seg in segments {
+    let part = match seg {
+      Segment::Lit(s) if s.is_empty() => continue,
+      Segment::Lit(s) => Inert(OrcString::from(s)).atom_expr(loc.clone()),
+      Segment::Arg(idx, spec) => {
+        let arg = args.0.0.get(idx).cloned().ok_or_else(|| {
+          let msg = format!("{} <= {idx}", args.0.0.len());
+          AssertionError::ext(loc.clone(), "Format argument index out of bounds", msg)
+        })?;
+        tpl::a2(tpl::C("std::string::format_value"), tpl::V(Inert(spec)), tpl::Slot)
+          .template(gen(&loc), [arg])
+      },
+    };
+    out =
+      tpl::a2(tpl::C("std::string::concat"), tpl::Slot, tpl::Slot).template(gen(&loc), [out, part]);
+  }
+  Ok(out)
+}
+
+pub(super) fn format_lib() -> ConstTree {
+  ConstTree::ns("std::string", [ConstTree::tree([
+    xfn_ent("format_value", [format_value]),
+    xfn_ent("format", [format]),
+  ])])
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Align, FormatKind, FormatSpec};
+  use crate::libs::std::number::Numeric;
+
+  fn render(spec: &str, num: f64) -> String {
+    let num = if num.fract() == 0.0 && 0.0 <= num {
+      Numeric::Uint(num as usize)
+    } else {
+      Numeric::new(num).unwrap()
+    };
+    FormatSpec::parse(spec).unwrap().render_num(num).unwrap()
+  }
+
+  #[test]
+  fn parse_spec() {
+    let spec = FormatSpec::parse("*^+#012.3x").unwrap();
+    assert_eq!((spec.fill, spec.align), ('*', Some(Align::Center)));
+    assert!(spec.sign && spec.alternate && spec.zero);
+    assert_eq!((spec.width, spec.precision, spec.kind), (12, Some(3), FormatKind::LowerHex));
+    assert_eq!(FormatSpec::parse("").unwrap(), FormatSpec::default());
+    assert!(FormatSpec::parse("5.").is_err());
+    assert!(FormatSpec::parse("xx").is_err());
+  }
+
+  #[test]
+  fn numbers() {
+    assert_eq!(render("08.3", 1.23456), "0001.235");
+    assert_eq!(render("08.3", -1.23456), "-001.235");
+    assert_eq!(render("x", 255.0), "ff");
+    assert_eq!(render("#06b", 5.0), "0b0101");
+    assert_eq!(render("+", 7.0), "+7");
+    assert_eq!(render(">6.1", 2.0), "   2.0");
+    assert_eq!(render(".2e", 1234.5), "1.23e3");
+  }
+
+  #[test]
+  fn strings() {
+    let render = |spec, s| FormatSpec::parse(spec).unwrap().render_str(s);
+    assert_eq!(render("<6", "ab"), "ab    ");
+    assert_eq!(render("-^7", "ab"), "--ab---");
+    assert_eq!(render(".3", "abcdef"), "abc");
+    assert_eq!(render("?", "a\"b"), "\"a\\\"b\"");
+  }
+}
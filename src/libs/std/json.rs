@@ -0,0 +1,403 @@
+//! `std::json` Conversion between JSON text and Orchid values. Objects become
+//! `std::map`, arrays become `std::list` and `null` becomes `option::none`.
+//! Encoding is extensible through the `std::json::encoding` protocol.
+
+use std::fmt::{self, Write as _};
+
+use super::number::Numeric;
+use super::protocol::gen_resolv;
+use super::string::OrcString;
+use crate::foreign::atom::Atomic;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::to_clause::{list, ToClause};
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tpl;
+use crate::gen::traits::Gen;
+use crate::gen::tree::{atom_ent, xfn_ent, ConstTree};
+use crate::interpreter::gen_nort::nort_gen;
+use crate::interpreter::nort::{Clause, Expr};
+use crate::location::CodeLocation;
+
+/// A JSON document. Objects keep their keys in insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  /// `null`
+  Null,
+  /// `true` or `false`
+  Bool(bool),
+  /// Any number
+  Num(Numeric),
+  /// A string
+  Str(String),
+  /// `[...]`
+  Array(Vec<Json>),
+  /// `{...}`
+  Object(Vec<(String, Json)>),
+}
+impl Json {
+  /// Serialize the document. When pretty printing, nested values are placed on
+  /// separate lines and indented by two spaces per level.
+  pub fn render(&self, pretty: bool) -> String {
+    let mut out = String::new();
+    self.render_to(&mut out, pretty.then_some(0)).expect("writing into a string");
+    out
+  }
+
+  fn render_to(&self, out: &mut String, indent: Option<usize>) -> fmt::Result {
+    let newline = |out: &mut String, depth: usize| match indent {
+      Some(_) => write!(out, "\n{}", "  ".repeat(depth)),
+      None => Ok(()),
+    };
+    let depth = indent.unwrap_or(0);
+    let inner = indent.map(|i| i + 1);
+    match self {
+      Json::Null => write!(out, "null"),
+      Json::Bool(b) => write!(out, "{b}"),
+      Json::Num(Numeric::Uint(n)) => write!(out, "{n}"),
+      Json::Num(Numeric::Float(f)) => write!(out, "{f}"),
+      Json::Str(s) => write_str(out, s),
+      Json::Array(v) if v.is_empty() => write!(out, "[]"),
+      Json::Object(v) if v.is_empty() => write!(out, "{{}}"),
+      Json::Array(items) => {
+        write!(out, "[")?;
+        for (i, item) in items.iter().enumerate() {
+          write!(out, "{}", if i == 0 { "" } else { "," })?;
+          newline(out, depth + 1)?;
+          item.render_to(out, inner)?;
+        }
+        newline(out, depth)?;
+        write!(out, "]")
+      },
+      Json::Object(entries) => {
+        write!(out, "{{")?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          write!(out, "{}", if i == 0 { "" } else { "," })?;
+          newline(out, depth + 1)?;
+          write_str(out, key)?;
+          write!(out, "{}", if indent.is_some() { ": " } else { ":" })?;
+          value.render_to(out, inner)?;
+        }
+        newline(out, depth)?;
+        write!(out, "}}")
+      },
+    }
+  }
+}
+impl InertPayload for Json {
+  const TYPE_STR: &'static str = "json";
+  fn strict_eq(&self, other: &Self) -> bool { self == other }
+}
+/// Convert into ordinary Orchid values. Arrays and objects are converted
+/// lazily.
+impl ToClause for Json {
+  fn to_clause(self, location: CodeLocation) -> Clause {
+    match self {
+      Json::Null => tpl::C("std::option::none").template(nort_gen(location), []),
+      Json::Bool(b) => Inert(b).atom_cls(),
+      Json::Num(n) => n.to_clause(location),
+      Json::Str(s) => Inert(OrcString::from(s)).atom_cls(),
+      Json::Array(items) => list(items).to_clause(location),
+      Json::Object(entries) => tpl::A(tpl::C("std::json::object"), tpl::Slot)
+        .template(nort_gen(location.clone()), [list(entries).to_clause(location)]),
+    }
+  }
+}
+
+fn write_str(out: &mut String, s: &str) -> fmt::Result {
+  write!(out, "\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => write!(out, "\\\"")?,
+      '\\' => write!(out, "\\\\")?,
+      '\n' => write!(out, "\\n")?,
+      '\r' => write!(out, "\\r")?,
+      '\t' => write!(out, "\\t")?,
+      '\x08' => write!(out, "\\b")?,
+      '\x0c' => write!(out, "\\f")?,
+      c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+      c => write!(out, "{c}")?,
+    }
+  }
+  write!(out, "\"")
+}
+
+/// Error message and byte offset
+type PResult<T> = Result<T, (String, usize)>;
+
+/// Recursive descent parser over the source text
+struct Parser<'a> {
+  src: &'a str,
+  pos: usize,
+}
+impl<'a> Parser<'a> {
+  fn tail(&self) -> &'a str { &self.src[self.pos..] }
+  fn peek(&self) -> Option<char> { self.tail().chars().next() }
+  fn fail<T>(&self, msg: impl Into<String>) -> PResult<T> { Err((msg.into(), self.pos)) }
+  fn ws(&mut self) {
+    let trimmed = self.tail().trim_start_matches([' ', '\t', '\n', '\r']);
+    self.pos = self.src.len() - trimmed.len();
+  }
+  fn eat(&mut self, prefix: &str) -> bool {
+    let found = self.tail().starts_with(prefix);
+    if found {
+      self.pos += prefix.len();
+    }
+    found
+  }
+  fn expect(&mut self, c: char) -> PResult<()> {
+    self.ws();
+    match self.peek() {
+      Some(found) if found == c => {
+        self.pos += 1;
+        Ok(())
+      },
+      Some(found) => self.fail(format!("expected {c:?}, found {found:?}")),
+      None => self.fail(format!("expected {c:?}, found end of input")),
+    }
+  }
+
+  fn value(&mut self) -> PResult<Json> {
+    self.ws();
+    match self.peek() {
+      None => self.fail("expected a value, found end of input"),
+      Some('{') => {
+        self.pos += 1;
+        let mut entries = Vec::<(String, Json)>::new();
+        self.ws();
+        if self.eat("}") {
+          return Ok(Json::Object(entries));
+        }
+        loop {
+          self.ws();
+          if self.peek() != Some('"') {
+            return self.fail("expected a string key");
+          }
+          let key = self.string()?;
+          self.expect(':')?;
+          let value = self.value()?;
+          // the last occurrence of a duplicate key wins
+          entries.retain(|(k, _)| *k != key);
+          entries.push((key, value));
+          self.ws();
+          if !self.eat(",") {
+            self.expect('}')?;
+            return Ok(Json::Object(entries));
+          }
+        }
+      },
+      Some('[') => {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.ws();
+        if self.eat("]") {
+          return Ok(Json::Array(items));
+        }
+        loop {
+          items.push(self.value()?);
+          self.ws();
+          if !self.eat(",") {
+            self.expect(']')?;
+            return Ok(Json::Array(items));
+          }
+        }
+      },
+      Some('"') => self.string().map(Json::Str),
+      Some('-' | '0'..='9') => self.number().map(Json::Num),
+      Some(_) if self.eat("null") => Ok(Json::Null),
+      Some(_) if self.eat("true") => Ok(Json::Bool(true)),
+      Some(_) if self.eat("false") => Ok(Json::Bool(false)),
+      Some(c) => self.fail(format!("unexpected {c:?}")),
+    }
+  }
+
+  fn number(&mut self) -> PResult<Numeric> {
+    let start = self.pos;
+    let digits = |p: &mut Self| {
+      let len = p.tail().find(|c: char| !c.is_ascii_digit()).unwrap_or(p.tail().len());
+      p.pos += len;
+      len
+    };
+    let negative = self.eat("-");
+    if digits(self) == 0 {
+      return self.fail("expected digits");
+    }
+    let mut integer = !negative;
+    if self.eat(".") {
+      integer = false;
+      if digits(self) == 0 {
+        return self.fail("expected digits after the decimal point");
+      }
+    }
+    if self.eat("e") || self.eat("E") {
+      integer = false;
+      let _ = self.eat("+") || self.eat("-");
+      if digits(self) == 0 {
+        return self.fail("expected digits in the exponent");
+      }
+    }
+    let text = &self.src[start..self.pos];
+    if let Some(n) = text.parse().ok().filter(|_| integer) {
+      return Ok(Numeric::Uint(n));
+    }
+    let f = text.parse::<f64>().expect("validated above");
+    Numeric::new(f).map_err(|_| (format!("{text} is out of range"), start))
+  }
+
+  fn string(&mut self) -> PResult<String> {
+    self.pos += 1; // opening quote
+    let mut out = String::new();
+    loop {
+      let c = match self.peek() {
+        None => return self.fail("unterminated string"),
+        Some(c) => c,
+      };
+      self.pos += c.len_utf8();
+      match c {
+        '"' => return Ok(out),
+        '\\' => {
+          let esc = match self.peek() {
+            None => return self.fail("unterminated string"),
+            Some(esc) => esc,
+          };
+          self.pos += esc.len_utf8();
+          out.push(match esc {
+            '"' | '\\' | '/' => esc,
+            'b' => '\x08',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => self.unicode_escape()?,
+            _ => return Err((format!("invalid escape {esc:?}"), self.pos - 2)),
+          })
+        },
+        c if c < ' ' => return Err(("control character in string".to_string(), self.pos - 1)),
+        c => out.push(c),
+      }
+    }
+  }
+
+  fn hex4(&mut self) -> PResult<u32> {
+    let hex = self.tail().get(..4).filter(|h| h.chars().all(|c| c.is_ascii_hexdigit()));
+    let code = hex.and_then(|h| u32::from_str_radix(h, 16).ok());
+    let code = code.map_or_else(|| self.fail("expected 4 hex digits"), Ok)?;
+    self.pos += 4;
+    Ok(code)
+  }
+
+  fn unicode_escape(&mut self) -> PResult<char> {
+    let high = self.hex4()?;
+    let code = match high {
+      0xd800..=0xdbff if self.eat("\\u") => {
+        let low = self.hex4()?;
+        if !(0xdc00..=0xdfff).contains(&low) {
+          return self.fail("expected a low surrogate");
+        }
+        0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+      },
+      code => code,
+    };
+    char::from_u32(code).map_or_else(|| self.fail("invalid code point"), Ok)
+  }
+}
+
+/// Parse a JSON document. Errors mention the 1-based line and column.
+pub fn parse(src: &str) -> Result<Json, String> {
+  let mut parser = Parser { src, pos: 0 };
+  let result = parser.value().and_then(|value| {
+    parser.ws();
+    match parser.peek() {
+      None => Ok(value),
+      Some(c) => parser.fail(format!("unexpected {c:?} after the value")),
+    }
+  });
+  result.map_err(|(msg, pos)| {
+    let line = src[..pos].matches('\n').count() + 1;
+    let column = src[..pos].rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    format!("{msg} at line {line} column {column}")
+  })
+}
+
+/// Convert a value to [Json]. Primitives are handled here, everything else is
+/// deferred to the `std::json::encoding` protocol.
+fn encode(WithLoc(loc, value): WithLoc<Expr>) -> Expr {
+  if let Ok(json) = value.clone().downcast::<Inert<Json>>() {
+    return json.atom_expr(loc);
+  }
+  if let Ok(Inert(s)) = value.clone().downcast::<Inert<OrcString>>() {
+    return Inert(Json::Str(s.get_string())).atom_expr(loc);
+  }
+  if let Ok(Inert(b)) = value.clone().downcast::<Inert<bool>>() {
+    return Inert(Json::Bool(b)).atom_expr(loc);
+  }
+  if let Some(n) = value.clause.request::<Numeric>() {
+    return Inert(Json::Num(n)).atom_expr(loc);
+  }
+  tpl::a2(gen_resolv("std::json::encoding"), tpl::Slot, tpl::Slot)
+    .template(nort_gen(loc), [value.clone(), value])
+}
+
+fn push(WithLoc(loc, array): WithLoc<Inert<Json>>, item: Inert<Json>) -> RTResult<Inert<Json>> {
+  match array.0 {
+    Json::Array(mut items) => {
+      items.push(item.0);
+      Ok(Inert(Json::Array(items)))
+    },
+    other => AssertionError::fail(loc, "a JSON array", other.render(false)),
+  }
+}
+
+fn insert(
+  WithLoc(loc, object): WithLoc<Inert<Json>>,
+  key: Inert<OrcString>,
+  value: Inert<Json>,
+) -> RTResult<Inert<Json>> {
+  match object.0 {
+    Json::Object(mut entries) => {
+      let key = key.0.get_string();
+      entries.retain(|(k, _)| *k != key);
+      entries.push((key, value.0));
+      Ok(Inert(Json::Object(entries)))
+    },
+    other => AssertionError::fail(loc, "a JSON object", other.render(false)),
+  }
+}
+
+pub(super) fn json_lib() -> ConstTree {
+  ConstTree::ns("std::json", [ConstTree::tree([
+    xfn_ent("parse", [|s: Inert<OrcString>| parse(s.0.as_str())]),
+    xfn_ent("encode", [encode]),
+    atom_ent("null", [Inert(Json::Null)]),
+    atom_ent("empty_array", [Inert(Json::Array(Vec::new()))]),
+    atom_ent("empty_object", [Inert(Json::Object(Vec::new()))]),
+    xfn_ent("push", [push]),
+    xfn_ent("insert", [insert]),
+    xfn_ent("render", [|json: Inert<Json>| json.0.render(false)]),
+    xfn_ent("render_pretty", [|json: Inert<Json>| json.0.render(true)]),
+  ])])
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse, Json};
+  use crate::libs::std::number::Numeric;
+
+  #[test]
+  fn roundtrip() {
+    let src = r#"{"a": [1, -2.5, true, null], "b": "x\"é😀"}"#;
+    let json = parse(src).expect("valid json");
+    let Json::Object(entries) = &json else { panic!("expected object") };
+    assert_eq!(entries[1].1, Json::Str("x\"é😀".to_string()));
+    assert_eq!(json.render(false), r#"{"a":[1,-2.5,true,null],"b":"x\"é😀"}"#);
+    assert_eq!(parse(&json.render(true)), Ok(json));
+    assert_eq!(parse("12"), Ok(Json::Num(Numeric::Uint(12))));
+  }
+
+  #[test]
+  fn errors() {
+    assert_eq!(parse("[1,\n  2,]"), Err("unexpected ']' at line 2 column 5".to_string()));
+    assert_eq!(parse("{} x"), Err("unexpected 'x' after the value at line 1 column 4".to_string()));
+    assert!(parse("\"abc").is_err());
+  }
+}
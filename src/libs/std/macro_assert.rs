@@ -0,0 +1,232 @@
+//! Assertions about the expansion of macros, written as
+//! `macro_assert <input> ==> <expected>`.
+//!
+//! The line generates a constant for each side. Macros aren't applied to the
+//! expected side, and `orcx test` compares it with the expansion of the input,
+//! ignoring locations.
+
+use std::slice;
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use intern_all::{i, Tok};
+use itertools::Itertools;
+
+use crate::error::{ProjectError, ProjectResult, Reporter};
+use crate::libs::parse_custom_line::custom_line;
+use crate::location::{CodeOrigin, SourceRange};
+use crate::name::Sym;
+use crate::parse::errors::ParseErrorKind;
+use crate::parse::frag::Frag;
+use crate::parse::lexer::Lexeme;
+use crate::parse::parse_plugin::{ParseLinePlugin, ParsePluginReq};
+use crate::parse::parsed::{self, Clause, Constant, Member, MemberKind, PType, SourceLineKind};
+use crate::pipeline::project::ConstReport;
+
+/// Comment attached to the constant holding the input of an assertion
+const MARKER: &str = "macro_assert";
+
+/// Name of the constant holding the input of the assertion at a position
+fn input_name(pos: usize) -> Tok<String> { i(&format!("__macro_assert_{pos}__")) }
+
+/// Name of the constant holding the expected output of an assertion
+fn expected_name(input: &Tok<String>) -> Tok<String> { i(&format!("{input}expected")) }
+
+/// A `macro_assert` line without the `==>` separator
+struct MissingSeparator;
+impl ParseErrorKind for MissingSeparator {
+  const DESCRIPTION: &'static str = "macro_assert lines must be of the form input ==> expected";
+}
+
+/// Parse one side of the assertion. The tokens are wrapped in a group so that
+/// they're compared as written.
+fn parse_side(frag: Frag, req: &dyn ParsePluginReq) -> ProjectResult<parsed::Expr> {
+  let (body, empty) = req.parse_exprv(frag, None)?;
+  req.expect_empty(empty)?;
+  Ok(parsed::Expr { value: Clause::S(PType::Par, Arc::new(body)), range: req.frag_loc(frag) })
+}
+
+/// The tokens of a side of the assertion
+fn side_tokens(side: &parsed::Expr) -> &[parsed::Expr] {
+  match &side.value {
+    Clause::S(PType::Par, body) => body,
+    _ => slice::from_ref(side),
+  }
+}
+
+/// Parser for the `macro_assert` line
+#[derive(Clone)]
+struct MacroAssertParser;
+impl ParseLinePlugin for MacroAssertParser {
+  fn parse(&self, req: &dyn ParsePluginReq) -> Option<ProjectResult<Vec<SourceLineKind>>> {
+    custom_line(req.frag(), i!(str: "macro_assert"), false, req).map(|res| {
+      let (_, tail, line_loc) = res?;
+      let mut depth = 0;
+      let sep = (tail.data.iter()).position(|e| {
+        match &e.lexeme {
+          Lexeme::LP(_) => depth += 1,
+          Lexeme::RP(_) => depth -= 1,
+          Lexeme::Name(n) => return depth == 0 && **n == "==>",
+          _ => (),
+        }
+        false
+      });
+      let sep = sep.ok_or_else(|| MissingSeparator.pack(line_loc.clone()))?;
+      let (input, rest) = tail.data.split_at(sep);
+      let (sep_ent, expected) = rest.split_first().expect("Index found above");
+      let input = parse_side(Frag::new(tail.fallback, input), req)?;
+      let expected = parse_side(Frag::new(sep_ent, expected), req)?;
+      let name = input_name(line_loc.range().start);
+      let constant = |name, value, expand| {
+        let kind = MemberKind::Constant(Constant { name, value, expand });
+        SourceLineKind::Member(Member { exported: false, kind })
+      };
+      Ok(vec![
+        SourceLineKind::Comment(MARKER.to_string()),
+        constant(name.clone(), input, true),
+        constant(expected_name(&name), expected, false),
+      ])
+    })
+  }
+}
+
+/// Collection of all the parser plugins defined here
+pub fn parsers() -> Vec<Box<dyn ParseLinePlugin>> { vec![Box::new(MacroAssertParser)] }
+
+/// Describe the differences between two sequences, ignoring locations
+fn diff(found: &[parsed::Expr], expected: &[parsed::Expr], path: &str, out: &mut Vec<String>) {
+  if found.len() != expected.len() {
+    let (found, expected) = (found.iter().join(" "), expected.iter().join(" "));
+    out.push(format!("{path}: expected `{expected}`, found `{found}`"));
+    return;
+  }
+  for (idx, (f, e)) in found.iter().zip(expected).enumerate() {
+    let path = format!("{path}/{idx}");
+    match (&f.value, &e.value) {
+      (Clause::S(fp, fb), Clause::S(ep, eb)) if fp == ep => diff(fb, eb, &path, out),
+      (Clause::Lambda(fa, fb), Clause::Lambda(ea, eb)) => {
+        diff(fa, ea, &format!("{path}/arg"), out);
+        diff(fb, eb, &format!("{path}/body"), out)
+      },
+      (Clause::Name(fname), Clause::Name(ename)) if fname == ename => (),
+      (Clause::Placeh(fph), Clause::Placeh(eph)) if fph == eph => (),
+      (Clause::Atom(_), Clause::Atom(_)) if f.to_string() == e.to_string() => (),
+      _ => out.push(format!("{path}: expected `{e}`, found `{f}`")),
+    }
+  }
+}
+
+/// Check every assertion among the constants of a project after the macros
+/// were run, and report the ones whose input didn't expand to the expected
+/// side in source order
+pub fn check_macro_asserts(consts: &HashMap<Sym, ConstReport>, reporter: &Reporter) {
+  let asserts = (consts.iter())
+    .filter(|(_, c)| c.comments.iter().any(|c| c.trim() == MARKER))
+    .sorted_by_key(|(_, c)| (c.range.path().to_string(), c.range.range().start));
+  for (name, input) in asserts {
+    let (last, module) = name[..].split_last().expect("Names are never empty");
+    let expected = Sym::new(module.iter().cloned().chain([expected_name(last)]));
+    let expected = expected.expect("Not empty");
+    let Some(expected) = consts.get(&expected) else {
+      reporter.report(MissingExpected { location: input.range.clone(), name: expected }.pack());
+      continue;
+    };
+    let mut differences = Vec::new();
+    diff(side_tokens(&input.value), side_tokens(&expected.value), "top", &mut differences);
+    if !differences.is_empty() {
+      reporter.report(MacroAssertFailed { location: input.range.clone(), differences }.pack())
+    }
+  }
+}
+
+/// A macro assertion whose input didn't expand to the expected output
+#[derive(Debug)]
+pub struct MacroAssertFailed {
+  /// Location of the assertion
+  pub location: SourceRange,
+  /// The differing subtrees, each prefixed with its path in the tree
+  pub differences: Vec<String>,
+}
+impl ProjectError for MacroAssertFailed {
+  const DESCRIPTION: &'static str = "Macro expansion differs from the expected output";
+  fn message(&self) -> String { self.differences.join("\n") }
+  fn one_position(&self) -> CodeOrigin { self.location.origin() }
+}
+
+/// The constant holding the expected side of an assertion is missing from the
+/// checked constants
+#[derive(Debug)]
+pub struct MissingExpected {
+  /// Location of the assertion
+  pub location: SourceRange,
+  /// Name of the missing constant
+  pub name: Sym,
+}
+impl ProjectError for MissingExpected {
+  const DESCRIPTION: &'static str = "The expected side of a macro assertion is missing";
+  fn message(&self) -> String { format!("{} was not found among the constants", self.name) }
+  fn one_position(&self) -> CodeOrigin { self.location.origin() }
+}
+
+#[cfg(test)]
+mod test {
+  use hashbrown::HashMap;
+
+  use super::{check_macro_asserts, MissingExpected};
+  use crate::error::{ProjectError, ProjectErrorObj, Reporter};
+  use crate::facade::loader::Loader;
+  use crate::facade::macro_runner::MacroRunner;
+  use crate::libs::std::std_system::StdConfig;
+  use crate::name::Sym;
+  use crate::pipeline::project::ConstReport;
+  use crate::sym;
+  use crate::virt_fs::{decl_file, DeclTree};
+
+  /// The constants of `src` after running a macro that repeats its argument
+  fn expand(src: &str) -> HashMap<Sym, ConstReport> {
+    let src = format!("macro twice $x =0x1p100=> ($x $x)\nconst main := 0\n{src}");
+    let env = Loader::new().add_system(StdConfig { impure: true });
+    let reporter = Reporter::new();
+    let root = DeclTree::ns("tree::main", [decl_file(&src)]);
+    let tree = env.load_project_main([sym!(tree::main::main)], root, &reporter);
+    let runner = MacroRunner::new(&tree, env.proc_macros(), Some(1000), &reporter);
+    let tree = runner.run_macros(tree, &reporter);
+    reporter.assert();
+    tree.all_consts()
+  }
+
+  fn check(src: &str) -> Option<Vec<ProjectErrorObj>> {
+    let reporter = Reporter::new();
+    check_macro_asserts(&expand(src), &reporter);
+    reporter.into_errors()
+  }
+
+  #[test]
+  fn passing() {
+    assert!(check("macro_assert twice a ==> (a a)").is_none());
+    assert!(check("macro_assert twice (b c) ==> ((b c) (b c))").is_none());
+  }
+
+  #[test]
+  fn failing() {
+    let errors = check("macro_assert twice a ==> (a b)").expect("the assertion fails");
+    let [error] = &errors[..] else { panic!("Expected one error, got {}", errors.len()) };
+    assert_eq!(error.message(), "top/0/1: expected `tree::main::b`, found `tree::main::a`");
+  }
+
+  #[test]
+  fn expected_side_is_not_expanded() {
+    assert!(check("macro_assert twice a ==> twice a").is_some());
+  }
+
+  #[test]
+  fn missing_expected_side() {
+    let mut consts = expand("macro_assert twice a ==> (a a)");
+    consts.retain(|name, _| !name[..].last().is_some_and(|n| n.ends_with("expected")));
+    let reporter = Reporter::new();
+    check_macro_asserts(&consts, &reporter);
+    let errors = reporter.into_errors().expect("the expected side is missing");
+    let [error] = &errors[..] else { panic!("Expected one error, got {}", errors.len()) };
+    assert_eq!(error.description(), MissingExpected::DESCRIPTION);
+  }
+}
@@ -2,17 +2,31 @@
 //! dependencies.
 pub mod arithmetic_error;
 pub mod binary;
+pub mod btree;
 mod bool;
+mod codegen;
+pub mod compare;
 mod conv;
 mod cross_pipeline;
+pub mod data;
 pub mod exit_status;
+pub mod format;
+pub mod json;
 mod inspect;
+pub mod macro_assert;
 pub mod number;
+pub mod operator;
 mod panic;
+mod pmatch;
 pub mod protocol;
+pub mod record;
 pub mod reflect;
+pub mod regex;
 pub mod runtime_error;
 mod state;
 pub mod std_system;
 pub mod string;
+#[cfg(test)]
+mod test_utils;
 pub mod tuple;
+pub mod vec;
@@ -121,14 +121,6 @@ pub fn remainder(a: Numeric, b: Numeric) -> RTResult<Numeric> {
   }
 }
 
-/// Tries to use integer comparison, casts to float otherwise
-pub fn less_than(a: Numeric, b: Numeric) -> Inert<bool> {
-  match (a, b) {
-    (Numeric::Uint(a), Numeric::Uint(b)) => Inert(a < b),
-    (a, b) => Inert(a.as_f64() < b.as_f64()),
-  }
-}
-
 pub(super) fn num_lib() -> ConstTree {
   ConstTree::ns("std::number", [ConstTree::tree([
     xfn_ent("add", [add]),
@@ -136,6 +128,5 @@ pub(super) fn num_lib() -> ConstTree {
     xfn_ent("multiply", [multiply]),
     xfn_ent("divide", [divide]),
     xfn_ent("remainder", [remainder]),
-    xfn_ent("less_than", [less_than]),
   ])])
 }
@@ -0,0 +1,223 @@
+//! User-declared operators, written as
+//! `operator <fixity> <level> <token> := <function>`.
+//!
+//! The fixity is one of `infixl`, `infixr`, `prefix` or `postfix`. The level
+//! is a number from 0 to 9, higher levels bind tighter, and it's converted
+//! into a priority in the binary operator band. Every operator must have its
+//! own priority because rules of equal priority are tried in declaration
+//! order, so collisions with the standard library or other `operator` lines
+//! are rejected.
+
+use hashbrown::HashMap;
+use intern_all::{i, Tok};
+use ordered_float::NotNan;
+
+use super::codegen::CodeGen;
+use super::number::Numeric;
+use crate::error::ProjectResult;
+use crate::libs::parse_custom_line::custom_line;
+use crate::location::SourceRange;
+use crate::parse::errors::ParseErrorKind;
+use crate::parse::lexer::{Entry, Lexeme};
+use crate::parse::parse_plugin::{ParseLinePlugin, ParsePluginReq};
+use crate::parse::parsed::{self, Member, MemberKind, PHClass, Rule, SourceLineKind};
+
+/// Operators of the standard library and their levels
+const STD_OPERATORS: &[(&str, f64)] = &[
+  ("*", 8.0),
+  ("/", 8.0),
+  ("%", 8.0),
+  ("+", 6.0),
+  ("-", 6.0),
+  ("<", 4.0),
+  (">", 4.0),
+  ("<=", 4.0),
+  (">=", 4.0),
+  ("==", 4.0),
+  ("!=", 4.0),
+  ("and", 2.0),
+  ("or", 2.0),
+];
+
+/// Convert a level to a priority. Level 8 is `0x1p36`, the priority of `*`,
+/// and every two levels down add `0x1p36` up to `0x5p36` at level 0.
+fn priority(level: NotNan<f64>) -> NotNan<f64> {
+  (NotNan::new(10.0).expect("Not NaN") - level) / 2.0 * 16f64.powi(36)
+}
+
+/// Position of the operator relative to its operands
+#[derive(Clone, Copy)]
+enum Fixity {
+  InfixL,
+  InfixR,
+  Prefix,
+  Postfix,
+}
+
+/// Something other than a fixity after the `operator` keyword
+struct BadFixity(Lexeme);
+impl ParseErrorKind for BadFixity {
+  const DESCRIPTION: &'static str = "Expected infixl, infixr, prefix or postfix";
+  fn message(&self) -> String { format!("{} is not a fixity", self.0) }
+}
+
+/// Something other than a number from 0 to 9 as the level of an operator
+struct BadLevel(Lexeme);
+impl ParseErrorKind for BadLevel {
+  const DESCRIPTION: &'static str = "Operator levels must be numbers from 0 to 9";
+  fn message(&self) -> String { format!("{} is not a valid operator level", self.0) }
+}
+
+/// An operator declared at the same level as another one
+struct PriorityCollision {
+  op: Tok<String>,
+  level: NotNan<f64>,
+  other: Tok<String>,
+}
+impl ParseErrorKind for PriorityCollision {
+  const DESCRIPTION: &'static str =
+    "Operators must have distinct levels, otherwise their precedence depends on rule order";
+  fn message(&self) -> String {
+    format!("{} at level {} has the same priority as {}", self.op, self.level, self.other)
+  }
+}
+
+fn parse_fixity(entry: &Entry, req: &dyn ParsePluginReq) -> ProjectResult<Fixity> {
+  match req.expect_name(entry)?.as_str() {
+    "infixl" => Ok(Fixity::InfixL),
+    "infixr" => Ok(Fixity::InfixR),
+    "prefix" => Ok(Fixity::Prefix),
+    "postfix" => Ok(Fixity::Postfix),
+    _ => Err(BadFixity(entry.lexeme.clone()).pack(req.range_loc(entry.range.clone()))),
+  }
+}
+
+fn parse_level(entry: &Entry, req: &dyn ParsePluginReq) -> ProjectResult<NotNan<f64>> {
+  let level = match &entry.lexeme {
+    Lexeme::Atom(a) => a.run().request::<Numeric>().map(|n| n.as_float()),
+    _ => None,
+  };
+  (level.filter(|l| (0.0..=9.0).contains(&**l)))
+    .ok_or_else(|| BadLevel(entry.lexeme.clone()).pack(req.range_loc(entry.range.clone())))
+}
+
+/// Generate the rule implementing an operator
+fn operator_rule(
+  fixity: Fixity,
+  op: Tok<String>,
+  prio: NotNan<f64>,
+  value: parsed::Expr,
+  range: SourceRange,
+) -> Rule {
+  let g = CodeGen(range);
+  let ph = |name: &str, prio: usize| g.placeh(name, PHClass::Vec { nonzero: true, prio });
+  let op = g.local(op);
+  let (pattern, args) = match fixity {
+    Fixity::InfixL => (vec![ph("lhs", 1), op, ph("rhs", 0)], vec!["lhs", "rhs"]),
+    Fixity::InfixR => (vec![ph("lhs", 0), op, ph("rhs", 1)], vec!["lhs", "rhs"]),
+    Fixity::Prefix => (vec![op, ph("operand", 0)], vec!["operand"]),
+    Fixity::Postfix => (vec![ph("operand", 0), op], vec!["operand"]),
+  };
+  let args = args.into_iter().map(|name| g.call(vec![ph(name, 0)]));
+  let template = vec![g.call([value].into_iter().chain(args).collect())];
+  Rule { pattern, prio, template, roles: vec![] }
+}
+
+/// The levels taken by the `operator` lines of the project being loaded
+#[derive(Default)]
+struct Declared(HashMap<NotNan<f64>, Tok<String>>);
+impl Declared {
+  /// Record the operator or return the one it collides with
+  fn declare(&mut self, op: &Tok<String>, level: NotNan<f64>) -> Option<Tok<String>> {
+    let std_op = STD_OPERATORS.iter().find(|(_, l)| *l == *level);
+    if let Some((name, _)) = std_op {
+      return Some(i(*name));
+    }
+    match self.0.try_insert(level, op.clone()) {
+      Ok(_) => None,
+      Err(e) => Some(e.entry.get().clone()),
+    }
+  }
+}
+
+/// Parser for the `operator` line. The levels declared so far are kept in the
+/// [crate::parse::context::LoadState] to detect collisions across files.
+#[derive(Clone)]
+struct OperatorParser;
+impl ParseLinePlugin for OperatorParser {
+  fn parse(&self, req: &dyn ParsePluginReq) -> Option<ProjectResult<Vec<SourceLineKind>>> {
+    custom_line(req.frag(), i!(str: "operator"), true, req).map(|res| {
+      let (exported, tail, line_loc) = res?;
+      let (fixity, tail) = req.pop(tail)?;
+      let fixity = parse_fixity(fixity, req)?;
+      let (level_ent, tail) = req.pop(tail)?;
+      let level = parse_level(level_ent, req)?;
+      let (op_ent, tail) = req.pop(tail)?;
+      let op = req.expect_name(op_ent)?;
+      let (walrus, tail) = req.pop(tail)?;
+      req.expect(Lexeme::Walrus, walrus)?;
+      let (body, empty) = req.parse_exprv(tail, None)?;
+      req.expect_empty(empty)?;
+      let value = req.vec_to_single(tail.fallback, body)?;
+      if let Some(other) = req.load_state().with(|d: &mut Declared| d.declare(&op, level)) {
+        let err = PriorityCollision { op, level, other };
+        return Err(err.pack(req.range_loc(op_ent.range.clone())));
+      }
+      let rule = operator_rule(fixity, op, priority(level), value, line_loc);
+      Ok(vec![SourceLineKind::Member(Member { exported, kind: MemberKind::Rule(rule) })])
+    })
+  }
+}
+
+/// Collection of all the parser plugins defined here
+pub fn parsers() -> Vec<Box<dyn ParseLinePlugin>> { vec![Box::new(OperatorParser)] }
+
+#[cfg(test)]
+mod test {
+  use crate::error::Reporter;
+  use crate::facade::loader::Loader;
+  use crate::libs::std::std_system::StdConfig;
+  use crate::libs::std::test_utils::run;
+  use crate::sym;
+  use crate::virt_fs::{decl_file, DeclTree};
+
+  #[test]
+  fn declaration() {
+    let src = "operator infixl 7 <+> := \\a. \\b. a * 10 + b\n\
+      operator prefix 5 ~ := \\a. a * 2\n\
+      operator postfix 9 ! := \\a. a + 1\n\
+      const main := 1 + 2 <+> 3 + ~ 4!";
+    assert_eq!(run(src).unwrap(), "34");
+  }
+
+  #[test]
+  fn associativity() {
+    let left = "operator infixl 7 <-> := \\a. \\b. a - b\nconst main := 10 <-> 3 <-> 2";
+    assert_eq!(run(left).unwrap(), "5");
+    let right = "operator infixr 7 <-> := \\a. \\b. a - b\nconst main := 10 <-> 3 <-> 2";
+    assert_eq!(run(right).unwrap(), "9");
+  }
+
+  #[test]
+  fn collision() {
+    let std = "operator infixl 6 <+> := \\a. \\b. a\nconst main := 1";
+    let err = run(std).unwrap_err();
+    assert!(err.contains("<+> at level 6 has the same priority as +"), "{err}");
+    let own = "operator infixl 7 <+> := \\a. \\b. a\noperator infixr 7 <-> := \\a. \\b. b\n\
+      const main := 1";
+    let err = run(own).unwrap_err();
+    assert!(err.contains("<-> at level 7 has the same priority as <+>"), "{err}");
+  }
+
+  #[test]
+  fn levels_reset_between_loads() {
+    let env = Loader::new().add_system(StdConfig { impure: true });
+    for op in ["<+>", "<->"] {
+      let reporter = Reporter::new();
+      let src = format!("operator infixl 7 {op} := \\a. \\b. a\nconst main := 1");
+      let root = DeclTree::ns("tree::main", [decl_file(&src)]);
+      env.load_project_main([sym!(tree::main::main)], root, &reporter);
+      reporter.assert();
+    }
+  }
+}
@@ -0,0 +1,66 @@
+//! `std::pmatch` Runtime support for the pattern matching macros
+
+use super::panic::orc_panic;
+use super::protocol::Tag;
+use super::string::OrcString;
+use crate::foreign::error::RTResult;
+use crate::foreign::inert::Inert;
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tpl;
+use crate::gen::traits::{Gen, GenClause};
+use crate::gen::tree::{xfn_ent, ConstTree};
+use crate::interpreter::gen_nort::nort_gen;
+use crate::interpreter::nort::Expr;
+use crate::sym;
+
+/// Panic because none of the arms of a `match` accepted the value. The value
+/// is included in the message if it can be converted to a string, otherwise
+/// its type tag is named.
+pub fn no_arms_match(WithLoc(loc, value): WithLoc<Expr>) -> RTResult<Expr> {
+  let message = match value.clause.request::<Tag>() {
+    Some(tag) if !tag.0.impls.contains_key(&sym!(std::string::conversion)) =>
+      format!("no arms match a value of type {}", tag.0.id),
+    Some(_) => return Ok(panic_with_value().template(nort_gen(loc), [value])),
+    None => match value.clause.request::<OrcString>() {
+      Some(s) => format!("no arms match {}", s.as_str()),
+      None => format!("no arms match {value}"),
+    },
+  };
+  orc_panic(Inert(OrcString::from(message))).map(|never| match never {})
+}
+
+fn panic_with_value() -> impl GenClause {
+  let prefix = tpl::V(Inert(OrcString::from("no arms match ")));
+  let message = tpl::a2(
+    tpl::C("std::string::concat"),
+    prefix,
+    tpl::A(tpl::C("std::string::convert"), tpl::Slot),
+  );
+  tpl::A(tpl::C("std::panic"), message)
+}
+
+pub(super) fn pmatch_lib() -> ConstTree {
+  ConstTree::ns("std::pmatch", [ConstTree::tree([xfn_ent("no_arms_match", [no_arms_match])])])
+}
+
+#[cfg(test)]
+mod test {
+  use crate::libs::std::test_utils::run;
+
+  #[test]
+  fn no_arms_match() {
+    let err = run("const main := match \"foo\" { \"bar\" => 1; }").unwrap_err();
+    assert!(err.contains("no arms match foo"), "{err}");
+    let err = run("const main := match 3 { 1 => 1; 2 => 2; }").unwrap_err();
+    assert!(err.contains("no arms match 3"), "{err}");
+    let opaque = "type opaque ()\nconst main := match opaque::wrap 1 { 1 => 1; }";
+    let err = run(opaque).unwrap_err();
+    assert!(err.contains("no arms match a value of type tree::main::opaque"), "{err}");
+  }
+
+  #[test]
+  fn first_matching_arm() {
+    let src = "const main := match 2 { 1 => \"one\"; 2 => \"two\"; _ => \"other\"; }";
+    assert_eq!(run(src).unwrap(), "two");
+  }
+}
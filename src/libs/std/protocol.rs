@@ -32,13 +32,15 @@ use crate::interpreter::nort;
 use crate::interpreter::nort::ClauseInst;
 use crate::libs::parse_custom_line::custom_line;
 use crate::location::SourceRange;
-use crate::name::{Sym, VName};
+use crate::name::{NameLike, Sym, VName};
+use crate::parse::errors::ParseErrorKind;
 use crate::parse::frag::Frag;
 use crate::parse::lexer::Lexeme;
 use crate::parse::parse_plugin::{ParseLinePlugin, ParsePluginReq};
 use crate::parse::parsed::{
   self, Constant, Member, MemberKind, ModuleBlock, PType, SourceLine, SourceLineKind,
 };
+use crate::sym;
 use crate::utils::ddispatch::Request;
 
 // TODO: write an example that thoroughly tests this module. Test rust-interop
@@ -217,9 +219,12 @@ fn parse_impl(
   })
 }
 
-struct Impl {
-  target: Sym,
-  value: parsed::Expr,
+/// An implementation of a protocol by a type or of a type by a protocol
+pub(super) struct Impl {
+  /// The ID key of the counterpart
+  pub(super) target: Sym,
+  /// The implementation
+  pub(super) value: parsed::Expr,
 }
 
 fn extract_impls(
@@ -270,9 +275,11 @@ fn parse_body_with_impls(
   req: &dyn ParsePluginReq,
   range: SourceRange,
   profile: ImplsProfile<impl WrapImpl>,
+  extra: impl IntoIterator<Item = Impl>,
 ) -> ProjectResult<Vec<SourceLine>> {
   let ImplsProfile { other_id, prelude, wrap, .. } = profile.clone();
-  let (mut lines, impls) = extract_impls(body, req, range.clone(), i(other_id))?;
+  let (mut lines, mut impls) = extract_impls(body, req, range.clone(), i(other_id))?;
+  impls.extend(extra);
   let line_loc = range.clone();
   let type_data = defer_to_runtime(
     range.clone(),
@@ -288,12 +295,110 @@ fn parse_body_with_impls(
       Ok(use_wrap(wrap, impls))
     },
   );
-  let type_data_line = Constant { name: i(TYPE_KEY), value: type_data.into_expr(range.clone()) };
+  let type_data = type_data.into_expr(range.clone());
+  let type_data_line = Constant { name: i(TYPE_KEY), value: type_data, expand: true };
   lines.extend(req.parse_entries(prelude, range.clone()));
   lines.push(MemberKind::Constant(type_data_line).into_line(true, range));
   Ok(lines)
 }
 
+/// Parse the body of a type, adding the given impls to those listed in it
+pub(super) fn parse_type_body(
+  body: Frag,
+  req: &dyn ParsePluginReq,
+  range: SourceRange,
+  extra: impl IntoIterator<Item = Impl>,
+) -> ProjectResult<Vec<SourceLine>> {
+  parse_body_with_impls(body, req, range, Tag::profile(), extra)
+}
+
+/// Parse the body of a type with the impls requested in its `derive` clause
+fn parse_derived_type_body(
+  body: Frag,
+  req: &dyn ParsePluginReq,
+  range: SourceRange,
+  derives: &[Derive],
+) -> ProjectResult<Vec<SourceLine>> {
+  let other_id = i(Tag::profile().other_id);
+  let extra = derives.iter().map(|d| d.to_impl(range.clone(), other_id.clone())).collect_vec();
+  parse_type_body(body, req, range, extra)
+}
+
+/// A protocol that can be implemented from the wrapped value with a
+/// `derive (...)` clause in a `type` or `as_type` line
+#[derive(Clone, Copy)]
+enum Derive {
+  /// `std::string::conversion`, printed as `name(value)`
+  Conversion,
+  /// `std::bool::equality`, the wrapped values are compared with `==`
+  Equality,
+  /// `std::compare::comparison`, the wrapped values are compared
+  Comparison,
+}
+impl Derive {
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "conversion" => Some(Self::Conversion),
+      "equality" => Some(Self::Equality),
+      "comparison" => Some(Self::Comparison),
+      _ => None,
+    }
+  }
+
+  fn to_impl(self, range: SourceRange, other_id: Tok<String>) -> Impl {
+    let (protocol, helper) = match self {
+      Self::Conversion => (sym!(std::string::conversion), sym!(std::protocol::derived_conversion)),
+      Self::Equality => (sym!(std::bool::equality), sym!(std::protocol::derived_equality)),
+      Self::Comparison => (sym!(std::compare::comparison), sym!(std::protocol::derived_comparison)),
+    };
+    let name = |sym: Sym| parsed::Clause::Name(sym).into_expr(range.clone());
+    let args = [name(helper), name(sym!(__type_id__)), name(sym!(unwrap))];
+    let value = parsed::Clause::S(PType::Par, Arc::new(args.to_vec())).into_expr(range.clone());
+    Impl { target: Sym::new(protocol.iter().chain([other_id])).unwrap(), value }
+  }
+}
+
+/// A name in a `derive (...)` clause that doesn't refer to a derivable protocol
+struct NotDerivable(Lexeme);
+impl ParseErrorKind for NotDerivable {
+  const DESCRIPTION: &'static str = "Only conversion, equality and comparison can be derived";
+  fn message(&self) -> String {
+    format!("{} cannot be derived. Try conversion, equality or comparison", self.0)
+  }
+}
+
+/// Parse an optional `derive (...)` clause at the start of the fragment
+fn parse_derive<'a>(
+  tail: Frag<'a>,
+  req: &dyn ParsePluginReq,
+) -> ProjectResult<(Vec<Derive>, Frag<'a>)> {
+  let (fst, rest) = req.pop(tail)?;
+  if fst.lexeme != Lexeme::Name(i!(str: "derive")) {
+    return Ok((Vec::new(), tail));
+  }
+  let rest = rest.trim();
+  let mut depth = 0;
+  let end = (rest.data.iter()).position(|e| {
+    match e.lexeme {
+      Lexeme::LP(_) => depth += 1,
+      Lexeme::RP(_) => depth -= 1,
+      _ => (),
+    }
+    depth == 0
+  });
+  let (block, tail) = rest.data.split_at(end.map_or(rest.data.len(), |i| i + 1));
+  let block = req.expect_block(Frag::new(fst, block), PType::Par)?;
+  let mut derives = Vec::new();
+  for entry in block.data.iter().filter(|e| !e.is_filler()) {
+    match &entry.lexeme {
+      Lexeme::Name(n) if **n == "," => (),
+      Lexeme::Name(n) if Derive::parse(n).is_some() => derives.extend(Derive::parse(n)),
+      lexeme => return Err(NotDerivable(lexeme.clone()).pack(req.range_loc(entry.range.clone()))),
+    }
+  }
+  Ok((derives, Frag::new(fst, tail).trim()))
+}
+
 #[derive(Clone)]
 struct ProtocolParser;
 impl ParseLinePlugin for ProtocolParser {
@@ -303,7 +408,7 @@ impl ParseLinePlugin for ProtocolParser {
       let (name, tail) = req.pop(tail)?;
       let name = req.expect_name(name)?;
       let tail = req.expect_block(tail, PType::Par)?;
-      let body = parse_body_with_impls(tail, req, line_loc, Protocol::profile())?;
+      let body = parse_body_with_impls(tail, req, line_loc, Protocol::profile(), [])?;
       let kind = MemberKind::Module(ModuleBlock { name, body });
       Ok(vec![SourceLineKind::Member(Member { exported, kind })])
     })
@@ -318,8 +423,9 @@ impl ParseLinePlugin for TypeParser {
       let (exported, tail, line_loc) = res?;
       let (name, tail) = req.pop(tail)?;
       let name = req.expect_name(name)?;
+      let (derives, tail) = parse_derive(tail.trim(), req)?;
       let tail = req.expect_block(tail, PType::Par)?;
-      let body = parse_body_with_impls(tail, req, line_loc, Tag::profile())?;
+      let body = parse_derived_type_body(tail, req, line_loc, &derives)?;
       let kind = MemberKind::Module(ModuleBlock { name, body });
       Ok(vec![SourceLineKind::Member(Member { exported, kind })])
     })
@@ -333,7 +439,7 @@ impl ParseLinePlugin for AsProtocolParser {
     custom_line(req.frag(), i!(str: "as_protocol"), false, req).map(|res| {
       let (_, tail, line_loc) = res?;
       let body = req.expect_block(tail, PType::Par)?;
-      parse_body_with_impls(body, req, line_loc, Protocol::profile())
+      parse_body_with_impls(body, req, line_loc, Protocol::profile(), [])
         .map(|v| v.into_iter().map(|e| e.kind).collect())
     })
   }
@@ -345,8 +451,9 @@ impl ParseLinePlugin for AsTypeParser {
   fn parse(&self, req: &dyn ParsePluginReq) -> Option<ProjectResult<Vec<SourceLineKind>>> {
     custom_line(req.frag(), i!(str: "as_type"), false, req).map(|res| {
       let (_, tail, line_loc) = res?;
+      let (derives, tail) = parse_derive(tail, req)?;
       let body = req.expect_block(tail, PType::Par)?;
-      parse_body_with_impls(body, req, line_loc, Tag::profile())
+      parse_derived_type_body(body, req, line_loc, &derives)
         .map(|v| v.into_iter().map(|e| e.kind).collect())
     })
   }
@@ -362,6 +469,11 @@ pub fn parsers() -> Vec<Box<dyn ParseLinePlugin>> {
   ]
 }
 
+/// Check whether a value carries the given type tag
+pub fn is(tag: Inert<Tag>, value: ClauseInst) -> Inert<bool> {
+  Inert(value.request::<Tag>().is_some_and(|t| t.strict_eq(&tag)))
+}
+
 /// Check and remove the type tag from a value
 pub fn unwrap(tag: Inert<Tag>, tagged: Inert<Tagged>) -> RTResult<nort::Expr> {
   if tagged.tag.strict_eq(&tag) {
@@ -405,6 +517,7 @@ pub fn protocol_lib() -> ConstTree {
   ConstTree::ns("std::protocol", [ConstTree::tree([
     xfn_ent("unwrap", [unwrap]),
     xfn_ent("wrap", [wrap]),
+    xfn_ent("is", [is]),
     xfn_ent("resolve", [resolve]),
     xfn_ent("break", [|t: Inert<Tagged>| t.0.value]),
   ])])
@@ -0,0 +1,186 @@
+//! `std::record` Type-tagged tuples with named fields, declared with a
+//! `record name (field, ...)` line.
+//!
+//! The line expands to a type module with a constructor called `new`, an
+//! accessor and a functional update `with_<field>` for every field, a
+//! `std::string::conversion` impl and a `name{field, field = pattern}` pattern.
+//!
+//! The field names in a pattern must refer to the accessors, so outside the
+//! type module they are either qualified as in `point{point::x}` or imported
+//! with `import self::point::(x, y)`.
+
+use std::sync::Arc;
+
+use intern_all::{i, Tok};
+use ordered_float::NotNan;
+
+use super::codegen::CodeGen;
+use super::protocol::{parse_type_body, Impl};
+use super::string::OrcString;
+use super::tuple::Tuple;
+use crate::error::ProjectResult;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::fn_bridge::Thunk;
+use crate::foreign::inert::Inert;
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tree::{xfn_ent, ConstTree};
+use crate::libs::parse_custom_line::custom_line;
+use crate::location::SourceRange;
+use crate::name::Sym;
+use crate::parse::errors::ParseErrorKind;
+use crate::parse::frag::Frag;
+use crate::parse::lexer::Lexeme;
+use crate::parse::parse_plugin::{ParseLinePlugin, ParsePluginReq};
+use crate::parse::parsed::{
+  self, Constant, Member, MemberKind, ModuleBlock, PHClass, PType, Rule, SourceLineKind,
+};
+use crate::sym;
+
+/// Something other than a name in the field list of a `record` line
+struct BadField(Lexeme);
+impl ParseErrorKind for BadField {
+  const DESCRIPTION: &'static str = "Record fields must be names separated by commas";
+  fn message(&self) -> String { format!("{} is not a valid field name", self.0) }
+}
+
+/// Parse the comma-separated list of field names
+fn parse_fields(block: Frag, req: &dyn ParsePluginReq) -> ProjectResult<Vec<Tok<String>>> {
+  let mut fields = Vec::new();
+  for (idx, entry) in block.data.iter().filter(|e| !e.is_filler()).enumerate() {
+    match &entry.lexeme {
+      Lexeme::Name(n) if idx % 2 == 1 && **n == "," => (),
+      Lexeme::Name(n) if idx % 2 == 0 && **n != "," && !fields.contains(n) =>
+        fields.push(n.clone()),
+      lexeme => return Err(BadField(lexeme.clone()).pack(req.range_loc(entry.range.clone()))),
+    }
+  }
+  Ok(fields)
+}
+
+/// Generate the type module for a record with the given fields
+fn record_body(
+  name: Tok<String>,
+  fields: &[Tok<String>],
+  req: &dyn ParsePluginReq,
+  range: SourceRange,
+) -> ProjectResult<Vec<parsed::SourceLine>> {
+  let g = CodeGen(range);
+  let num = |n: usize| g.atom(Inert(n));
+  let names = (fields.iter().rev()).fold(g.name(sym!(std::list::end)), |tail, f| {
+    g.call(vec![g.name(sym!(std::list::cons)), g.atom(Inert(OrcString::from(f.clone()))), tail])
+  });
+  let show = vec![g.name(sym!(std::record::show)), g.name(sym!(__type_id__)), names];
+  let show = g.call(show.into_iter().chain([g.name(sym!(unwrap))]).collect());
+  let conversion = Impl { target: sym!(std::string::conversion::__protocol_id__), value: show };
+  let empty = Frag::new(req.frag().fallback, &[]);
+  let mut lines = parse_type_body(empty, req, g.range(), [conversion])?;
+  let mut constant = |name: Tok<String>, value: parsed::Expr| {
+    let kind = MemberKind::Constant(Constant { name, value, expand: true });
+    lines.push(kind.into_line(true, g.range()))
+  };
+  let ctor = vec![g.name(sym!(std::record::constructor)), g.name(sym!(wrap)), num(fields.len())];
+  constant(i!(str: "new"), g.call(ctor));
+  for (idx, field) in fields.iter().enumerate() {
+    let get = vec![g.name(sym!(std::record::get)), g.name(sym!(unwrap)), num(idx)];
+    constant(field.clone(), g.call(get));
+    let with = [sym!(std::record::with), sym!(wrap), sym!(unwrap)].map(|n| g.name(n));
+    let with = with.into_iter().chain([num(idx)]).collect();
+    constant(i(&format!("with_{field}")), g.call(with));
+  }
+  let vec_ph = |name: &str| g.placeh(name, PHClass::Vec { nonzero: false, prio: 0 });
+  let prio = |p: f64| NotNan::new(p).expect("Not NaN");
+  let mut rule = |pattern: Vec<parsed::Expr>, prio: NotNan<f64>, template: Vec<parsed::Expr>| {
+    let kind = MemberKind::Rule(Rule { pattern, prio, template, roles: vec![] });
+    lines.push(kind.into_line(false, g.range()))
+  };
+  let self_name = Sym::new([i!(str: "super"), name]).expect("Not empty");
+  let fields_block = g.expr(parsed::Clause::S(PType::Curl, Arc::new(vec![vec_ph("fields")])));
+  rule(
+    vec![g.name(sym!(std::pmatch::request)), g.call(vec![g.name(self_name), fields_block])],
+    prio(16f64.powi(230)),
+    vec![
+      g.name(sym!(std::record::fields_pattern)),
+      g.call(vec![g.name(sym!(std::protocol::is)), g.name(sym!(__type_data__))]),
+      g.call(vec![
+        g.name(sym!(std::record::fields_walker)),
+        g.name(sym!(__type_id__)),
+        g.name(sym!(std::macro::comma_list)),
+        g.call(vec![vec_ph("fields")]),
+      ]),
+    ],
+  );
+  // Fields are identified by the fully qualified name of their accessor
+  for field in fields {
+    rule(
+      vec![g.name(sym!(std::record::accessor)), g.name(sym!(__type_id__)), g.local(field.clone())],
+      prio(16f64.powi(254)),
+      vec![g.local(field.clone())],
+    );
+  }
+  Ok(lines)
+}
+
+#[derive(Clone)]
+struct RecordParser;
+impl ParseLinePlugin for RecordParser {
+  fn parse(&self, req: &dyn ParsePluginReq) -> Option<ProjectResult<Vec<SourceLineKind>>> {
+    custom_line(req.frag(), i!(str: "record"), true, req).map(|res| {
+      let (exported, tail, line_loc) = res?;
+      let (name, tail) = req.pop(tail)?;
+      let name = req.expect_name(name)?;
+      let block = req.expect_block(tail, PType::Par)?;
+      let fields = parse_fields(block, req)?;
+      let body = record_body(name.clone(), &fields, req, line_loc)?;
+      let kind = MemberKind::Module(ModuleBlock { name, body });
+      Ok(vec![SourceLineKind::Member(Member { exported, kind })])
+    })
+  }
+}
+
+/// Collection of all the parser plugins defined here
+pub fn parsers() -> Vec<Box<dyn ParseLinePlugin>> { vec![Box::new(RecordParser)] }
+
+/// Replace an element of a tuple
+fn set(
+  WithLoc(loc, Inert(tuple)): WithLoc<Inert<Tuple>>,
+  idx: Inert<usize>,
+  value: Thunk,
+) -> RTResult<Inert<Tuple>> {
+  let mut items = Arc::unwrap_or_clone(tuple.0);
+  let len = items.len();
+  let slot = items.get_mut(idx.0).ok_or_else(|| {
+    AssertionError::ext(loc, "Tuple index out of bounds", format!("{len} <= {idx}"))
+  })?;
+  *slot = value.0;
+  Ok(Inert(Tuple(Arc::new(items))))
+}
+
+pub(super) fn record_lib() -> ConstTree {
+  ConstTree::ns("std::record", [ConstTree::tree([xfn_ent("set", [set])])])
+}
+
+#[cfg(test)]
+mod test {
+  use crate::libs::std::test_utils::run;
+
+  const POINT: &str = "import std::record\nrecord point (x, y)\n";
+
+  #[test]
+  fn field_patterns() {
+    let qualified = "const main := match point::new 1 2 {\n\
+      point{point::x = 1, point::y} => point::y;\n\
+    }";
+    assert_eq!(run(&format!("{POINT}{qualified}")).unwrap(), "2");
+    let imported = "import self::point::(x, y)\n\
+      const main := match point::new 1 2 { point{x, y = 1} => 0; point{x, y} => x + y; }";
+    assert_eq!(run(&format!("{POINT}{imported}")).unwrap(), "3");
+  }
+
+  #[test]
+  fn foreign_field_name() {
+    let foreign = "module other (export const x := 1)\n\
+      const main := match point::new 1 2 { point{other::x} => 0; }";
+    let err = run(&format!("{POINT}{foreign}")).unwrap_err();
+    assert!(err.contains("std::record::accessor"), "{err}");
+  }
+}
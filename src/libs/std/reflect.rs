@@ -13,7 +13,7 @@ use crate::foreign::inert::{Inert, InertPayload};
 use crate::foreign::try_from_expr::WithLoc;
 use crate::gen::tree::{xfn_ent, ConstTree};
 use crate::interpreter::nort::{self, Clause};
-use crate::name::Sym;
+use crate::name::{NameLike, Sym};
 
 impl InertPayload for Sym {
   const TYPE_STR: &'static str = "SymbolName";
@@ -69,6 +69,7 @@ pub(super) fn reflect_lib() -> ConstTree {
   ConstTree::ns("std::reflect", [ConstTree::tree([
     xfn_ent("ref_equal", [|l: Inert<RefEqual>, r: Inert<RefEqual>| Inert(l.0.id() == r.0.id())]),
     xfn_ent("modname", [|WithLoc(loc, _): WithLoc<nort::Expr>| Inert(loc.module)]),
+    xfn_ent("basename", [|s: Inert<Sym>| s.0.last().to_string()]),
     xfn_ent("symbol", [|s: Inert<OrcString>| {
       Sym::parse(s.0.as_str())
         .map(Inert)
@@ -0,0 +1,223 @@
+//! `std::regex` Regular expressions. Patterns are compiled once and cached, so
+//! functions also accept plain strings wherever a regex is expected.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use hashbrown::HashMap;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::runtime_error::RuntimeError;
+use super::string::OrcString;
+use super::tuple::Tuple;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::to_clause::{list, ToClause};
+use crate::foreign::try_from_expr::{TryFromExpr, WithLoc};
+use crate::gen::tree::{xfn_ent, ConstTree};
+use crate::interpreter::nort::Expr;
+use crate::utils::ddispatch::Request;
+
+/// The least recently used pattern is dropped when the cache grows past this
+/// many entries
+const CACHE_LIMIT: usize = 256;
+
+/// Compiled patterns with the time they were last used
+#[derive(Default)]
+struct PatternCache {
+  patterns: HashMap<String, (OrcRegex, u64)>,
+  clock: u64,
+}
+impl PatternCache {
+  fn get(&mut self, pattern: &str) -> Option<OrcRegex> {
+    self.clock += 1;
+    let (re, used) = self.patterns.get_mut(pattern)?;
+    *used = self.clock;
+    Some(re.clone())
+  }
+
+  fn insert(&mut self, pattern: String, re: OrcRegex) {
+    if CACHE_LIMIT <= self.patterns.len() {
+      let lru = self.patterns.iter().min_by_key(|(_, (_, used))| *used);
+      let lru = lru.map(|(k, _)| k.clone()).expect("The limit is not zero");
+      self.patterns.remove(&lru);
+    }
+    self.clock += 1;
+    self.patterns.insert(pattern, (re, self.clock));
+  }
+}
+
+static CACHE: Lazy<Mutex<PatternCache>> = Lazy::new(|| Mutex::new(PatternCache::default()));
+
+/// A compiled regular expression
+#[derive(Clone)]
+pub struct OrcRegex(pub Arc<Regex>);
+impl OrcRegex {
+  /// Compile a pattern or retrieve it from the cache
+  pub fn compile(pattern: &str) -> RTResult<Self> {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(re) = cache.get(pattern) {
+      return Ok(re);
+    }
+    let re = Regex::new(pattern)
+      .map_err(|e| RuntimeError::ext(e.to_string(), "compiling regular expression"))?;
+    let re = OrcRegex(Arc::new(re));
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+  }
+}
+impl fmt::Debug for OrcRegex {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Regex({})", self.0) }
+}
+impl InertPayload for OrcRegex {
+  const TYPE_STR: &'static str = "regex";
+  fn strict_eq(&self, other: &Self) -> bool { self.0.as_str() == other.0.as_str() }
+  fn respond(&self, mut request: Request) {
+    request.serve_with(|| OrcString::from(self.0.as_str()))
+  }
+}
+impl TryFromExpr for OrcRegex {
+  fn from_expr(expr: Expr) -> RTResult<Self> {
+    if let Ok(Inert(re)) = expr.clone().downcast() {
+      return Ok(re);
+    }
+    match expr.clone().downcast::<Inert<OrcString>>() {
+      Ok(Inert(pattern)) => Self::compile(pattern.as_str()),
+      Err(_) => AssertionError::fail(expr.location(), "regex or string", format!("{expr}")),
+    }
+  }
+}
+
+/// The groups captured by a single match of a regex
+#[derive(Clone)]
+pub struct Captures {
+  groups: Arc<Vec<Option<String>>>,
+  names: Arc<HashMap<String, usize>>,
+}
+impl fmt::Debug for Captures {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Captures").field(&self.groups).finish()
+  }
+}
+impl InertPayload for Captures {
+  const TYPE_STR: &'static str = "regex captures";
+}
+
+/// Convert a byte offset into a grapheme index, which is what the rest of
+/// `std::string` uses.
+fn grapheme_idx(s: &str, byte: usize) -> usize { s[..byte].graphemes(true).count() }
+
+fn find_all(re: OrcRegex, s: Inert<OrcString>) -> impl ToClause {
+  let matches = (re.0.find_iter(s.0.as_str()))
+    .map(|m| {
+      let (start, text) = (grapheme_idx(&s.0, m.start()), m.as_str().to_string());
+      let end = start + text.graphemes(true).count();
+      (text, Inert(start), Inert(end))
+    })
+    .collect_vec();
+  list(matches)
+}
+
+fn captures(re: OrcRegex, s: Inert<OrcString>) -> Option<Inert<Captures>> {
+  let caps = re.0.captures(s.0.as_str())?;
+  let groups = caps.iter().map(|m| m.map(|m| m.as_str().to_string())).collect_vec();
+  let names = (re.0.capture_names().enumerate())
+    .filter_map(|(i, name)| Some((name?.to_string(), i)))
+    .collect();
+  Some(Inert(Captures { groups: Arc::new(groups), names: Arc::new(names) }))
+}
+
+/// Tuple of the strings captured by each group, with the empty string for
+/// groups that did not participate in the match
+fn groups(re: OrcRegex, WithLoc(loc, s): WithLoc<Inert<OrcString>>) -> Option<Inert<Tuple>> {
+  let caps = re.0.captures(s.0.as_str())?;
+  let groups = (caps.iter().skip(1))
+    .map(|m| m.map_or("", |m| m.as_str()).to_string().to_expr(loc.clone()))
+    .collect();
+  Some(Inert(Tuple(Arc::new(groups))))
+}
+
+pub(super) fn regex_lib() -> ConstTree {
+  ConstTree::ns("std::regex", [ConstTree::tree([
+    xfn_ent("new", [|re: OrcRegex| Inert(re)]),
+    xfn_ent("is_match", [|re: OrcRegex, s: Inert<OrcString>| Inert(re.0.is_match(s.0.as_str()))]),
+    xfn_ent("find_all", [find_all]),
+    xfn_ent("captures", [captures]),
+    xfn_ent("group", [|caps: Inert<Captures>, i: Inert<usize>| {
+      caps.0.groups.get(i.0).cloned().flatten()
+    }]),
+    xfn_ent("named", [|caps: Inert<Captures>, name: Inert<OrcString>| {
+      let i = *caps.0.names.get(name.0.as_str())?;
+      caps.0.groups[i].clone()
+    }]),
+    xfn_ent("groups", [groups]),
+    xfn_ent("replace", [|re: OrcRegex, s: Inert<OrcString>, with: Inert<OrcString>| {
+      re.0.replace_all(s.0.as_str(), with.0.as_str()).into_owned()
+    }]),
+    xfn_ent("split", [|re: OrcRegex, s: Inert<OrcString>| {
+      list(re.0.split(s.0.as_str()).map(str::to_string).collect_vec())
+    }]),
+  ])])
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use regex::Regex;
+
+  use super::{OrcRegex, PatternCache, CACHE_LIMIT};
+  use crate::libs::std::test_utils::run;
+
+  #[test]
+  fn cache_evicts_least_recently_used() {
+    let mut cache = PatternCache::default();
+    let re = OrcRegex(Arc::new(Regex::new("a").unwrap()));
+    for i in 0..CACHE_LIMIT {
+      cache.insert(i.to_string(), re.clone());
+    }
+    assert!(cache.get("0").is_some());
+    cache.insert("new".to_string(), re);
+    assert!(cache.get("0").is_some(), "recently used");
+    assert!(cache.get("1").is_none(), "least recently used");
+    assert_eq!(cache.patterns.len(), CACHE_LIMIT);
+  }
+
+  fn run_expr(expr: &str) -> Result<String, String> {
+    run(&format!("import std::regex\nconst main := {expr}"))
+  }
+
+  #[test]
+  fn matching() {
+    assert_eq!(run_expr(r#"regex::is_match "^a+$" "aaa""#).unwrap(), "true");
+    assert_eq!(run_expr(r#"regex::is_match "^a+$" "aba""#).unwrap(), "false");
+    let pattern = r#"match "me@host" { regex::matches "(\\w+)@(\\w+)" [u, h] => h ++ u; }"#;
+    assert_eq!(run_expr(pattern).unwrap(), "hostme");
+  }
+
+  #[test]
+  fn captures() {
+    let caps = r#"regex::captures "(?P<user>\\w+)@(\\w+)" "me@host""#;
+    let named = format!(r#"option::flatmap ({caps}) \c. regex::named c "user""#);
+    assert_eq!(run_expr(&format!(r#"option::fallback ({named}) "none""#)).unwrap(), "me");
+    let group = format!(r#"option::flatmap ({caps}) \c. regex::group c 2"#);
+    assert_eq!(run_expr(&format!(r#"option::fallback ({group}) "none""#)).unwrap(), "host");
+    let missing = r#"option::fallback (regex::captures "x" "me@host") "none""#;
+    assert_eq!(run_expr(missing).unwrap(), "none");
+  }
+
+  #[test]
+  fn replace() {
+    assert_eq!(run_expr(r##"regex::replace "[0-9]" "a1b22" "#""##).unwrap(), "a#b##");
+    assert_eq!(run_expr(r#"regex::replace "(\\w)(\\w)" "ab" "$2$1""#).unwrap(), "ba");
+  }
+
+  #[test]
+  fn invalid_pattern() {
+    let err = run_expr(r#"regex::is_match "(" "a""#).unwrap_err();
+    assert!(err.contains("compiling regular expression"), "{err}");
+  }
+}
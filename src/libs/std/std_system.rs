@@ -6,16 +6,27 @@ use rust_embed::RustEmbed;
 
 use super::binary::bin_lib;
 use super::bool::bool_lib;
+use super::btree::btree_lib;
+use super::compare::compare_lib;
 use super::conv::conv_lib;
+use super::data::parsers as data_parsers;
 use super::exit_status::exit_status_lib;
+use super::format::format_lib;
 use super::inspect::inspect_lib;
+use super::json::json_lib;
+use super::macro_assert::parsers as macro_assert_parsers;
 use super::number::num_lib;
+use super::operator::parsers as operator_parsers;
 use super::panic::panic_lib;
+use super::pmatch::pmatch_lib;
 use super::protocol::{parsers, protocol_lib};
+use super::record::{parsers as record_parsers, record_lib};
 use super::reflect::reflect_lib;
+use super::regex::regex_lib;
 use super::state::{state_handlers, state_lib};
-use super::string::{str_lib, StringLexer};
+use super::string::{StringLexer, str_lib};
 use super::tuple::tuple_lib;
+use super::vec::vec_lib;
 use crate::facade::system::{IntoSystem, System};
 use crate::gen::tree::{ConstCombineErr, ConstTree};
 use crate::location::CodeGenInfo;
@@ -41,15 +52,23 @@ impl StdConfig {
   fn stdlib(&self) -> Result<ConstTree, ConstCombineErr> {
     let pure_tree = tuple_lib()
       .combine(bin_lib())?
+      .combine(btree_lib())?
       .combine(bool_lib())?
+      .combine(compare_lib())?
       .combine(conv_lib())?
       .combine(exit_status_lib())?
+      .combine(format_lib())?
+      .combine(json_lib())?
       .combine(num_lib())?
       .combine(panic_lib())?
+      .combine(pmatch_lib())?
       .combine(protocol_lib())?
+      .combine(record_lib())?
       .combine(reflect_lib())?
+      .combine(regex_lib())?
       .combine(state_lib())?
-      .combine(str_lib())?;
+      .combine(str_lib())?
+      .combine(vec_lib())?;
     if !self.impure {
       return Ok(pure_tree);
     }
@@ -72,7 +91,13 @@ impl IntoSystem<'static> for StdConfig {
       }],
       handlers: state_handlers(),
       lexer_plugins: vec![Box::new(StringLexer)],
-      line_parsers: parsers(),
+      line_parsers: (parsers().into_iter())
+        .chain(record_parsers())
+        .chain(data_parsers())
+        .chain(operator_parsers())
+        .chain(macro_assert_parsers())
+        .collect(),
+      proc_macros: vec![],
     }
   }
 }
@@ -10,13 +10,16 @@ use intern_all::{i, Tok};
 use itertools::Itertools;
 use unicode_segmentation::UnicodeSegmentation;
 
+use super::format::{BadFormatSpec, FormatSpec};
+use super::number::Numeric;
 use super::protocol::{gen_resolv, Protocol};
 use super::runtime_error::RuntimeError;
 use crate::error::{ProjectErrorObj, ProjectResult};
+use crate::facade::macro_runner::ErrorMessage;
 use crate::foreign::atom::{AtomGenerator, Atomic};
-use crate::foreign::error::RTResult;
+use crate::foreign::error::{AssertionError, RTResult};
 use crate::foreign::inert::{Inert, InertPayload};
-use crate::foreign::to_clause::ToClause;
+use crate::foreign::to_clause::{list, ToClause};
 use crate::foreign::try_from_expr::{TryFromExpr, WithLoc};
 use crate::gen::tpl;
 use crate::gen::traits::Gen;
@@ -29,6 +32,7 @@ use crate::parse::errors::ParseErrorKind;
 use crate::parse::lex_plugin::{LexPluginRecur, LexPluginReq, LexerPlugin};
 use crate::parse::lexer::{Entry, LexRes, Lexeme};
 use crate::parse::parsed::PType;
+use crate::utils::ddispatch::Request;
 use crate::utils::iter_find::iter_find;
 
 /// An Orchid string which may or may not be interned
@@ -105,6 +109,9 @@ impl PartialEq for OrcString {
 impl InertPayload for OrcString {
   const TYPE_STR: &'static str = "OrcString";
   fn strict_eq(&self, other: &Self) -> bool { self == other }
+  fn respond(&self, mut request: Request) {
+    request.serve_with(|| ErrorMessage(self.as_str().to_string()))
+  }
 }
 
 impl ToClause for String {
@@ -163,6 +170,52 @@ pub(super) fn str_lib() -> ConstTree {
         x => x,
       })
     }]),
+    xfn_ent("to_upper", [|s: Inert<OrcString>| s.0.to_uppercase()]),
+    xfn_ent("to_lower", [|s: Inert<OrcString>| s.0.to_lowercase()]),
+    xfn_ent("trim", [|s: Inert<OrcString>| s.0.trim().to_string()]),
+    xfn_ent("trim_start", [|s: Inert<OrcString>| s.0.trim_start().to_string()]),
+    xfn_ent("trim_end", [|s: Inert<OrcString>| s.0.trim_end().to_string()]),
+    xfn_ent("split_by", [|s: Inert<OrcString>, sep: Inert<OrcString>| {
+      list(s.0.split(sep.0.as_str()).map(str::to_string).collect_vec())
+    }]),
+    xfn_ent("replace", [|s: Inert<OrcString>, from: Inert<OrcString>, to: Inert<OrcString>| {
+      s.0.replace(from.0.as_str(), to.0.as_str())
+    }]),
+    xfn_ent("starts_with", [|s: Inert<OrcString>, prefix: Inert<OrcString>| {
+      Inert(s.0.starts_with(prefix.0.as_str()))
+    }]),
+    xfn_ent("strip_prefix", [|s: Inert<OrcString>, prefix: Inert<OrcString>| {
+      s.0.strip_prefix(prefix.0.as_str()).map(|rest| Inert(OrcString::from(rest)))
+    }]),
+    xfn_ent("ends_with", [|s: Inert<OrcString>, suffix: Inert<OrcString>| {
+      Inert(s.0.ends_with(suffix.0.as_str()))
+    }]),
+    xfn_ent("contains", [|s: Inert<OrcString>, needle: Inert<OrcString>| {
+      Inert(s.0.contains(needle.0.as_str()))
+    }]),
+    xfn_ent("reverse", [|s: Inert<OrcString>| s.0.graphemes(true).rev().collect::<String>()]),
+    xfn_ent("graphemes", [|s: Inert<OrcString>| {
+      list(s.0.graphemes(true).map(str::to_string).collect_vec())
+    }]),
+    xfn_ent("codepoints", [|s: Inert<OrcString>| {
+      list(s.0.chars().map(|c| Inert(c as usize)).collect_vec())
+    }]),
+    xfn_ent("from_codepoint", [|WithLoc(loc, c): WithLoc<Inert<usize>>| {
+      u32::try_from(c.0)
+        .ok()
+        .and_then(char::from_u32)
+        .map(String::from)
+        .ok_or_else(|| AssertionError::ext(loc, "a unicode code point", c.0.to_string()))
+    }]),
+    xfn_ent("parse_int", [|s: Inert<OrcString>, WithLoc(loc, radix): WithLoc<Inert<usize>>| {
+      parse_int(s.0.as_str(), radix.0, loc)
+    }]),
+    xfn_ent("pad_start", [|s: Inert<OrcString>, width: Inert<usize>, fill: Inert<OrcString>| {
+      pad(s.0.as_str(), width.0, fill.0.as_str(), false)
+    }]),
+    xfn_ent("pad_end", [|s: Inert<OrcString>, width: Inert<usize>, fill: Inert<OrcString>| {
+      pad(s.0.as_str(), width.0, fill.0.as_str(), true)
+    }]),
     xfn_ent("convert", [|WithLoc(loc, a): WithLoc<Expr>| match a.clone().downcast() {
       Ok(str) => Inert::<OrcString>::atom_expr(str, loc),
       Err(_) => match a.clause.request::<OrcString>() {
@@ -174,6 +227,38 @@ pub(super) fn str_lib() -> ConstTree {
   ])])
 }
 
+/// Parse an integer in the given radix with an optional leading sign. Integers
+/// are unsigned, so negative numbers are an error.
+fn parse_int(s: &str, radix: usize, loc: CodeLocation) -> RTResult<Option<Numeric>> {
+  if !(2..=36).contains(&radix) {
+    return AssertionError::fail(loc, "a radix between 2 and 36", radix.to_string());
+  }
+  let (negative, digits) = match s.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, s.strip_prefix('+').unwrap_or(s)),
+  };
+  // from_str_radix would accept a second sign
+  if digits.starts_with(['+', '-']) {
+    return Ok(None);
+  }
+  let num = match usize::from_str_radix(digits, radix as u32) {
+    Err(_) => return Ok(None),
+    Ok(num) => num,
+  };
+  match negative && num != 0 {
+    true => AssertionError::fail(loc, "a nonnegative integer", s.to_string()),
+    false => Ok(Some(Numeric::Uint(num))),
+  }
+}
+
+/// Repeat `fill` on the given side until the string is `width` graphemes long.
+/// The last repetition of `fill` may be truncated.
+fn pad(s: &str, width: usize, fill: &str, at_end: bool) -> String {
+  let missing = width.saturating_sub(s.graphemes(true).count());
+  let padding = fill.graphemes(true).cycle().take(missing).collect::<String>();
+  if at_end { format!("{s}{padding}") } else { format!("{padding}{s}") }
+}
+
 /// Reasons why [parse_string] might fail. See [StringError]
 enum StringErrorKind {
   /// A unicode escape sequence wasn't followed by 4 hex digits
@@ -281,21 +366,43 @@ impl LexerPlugin for StringLexer {
         if let Some(rest) = txt.strip_prefix("${") {
           let mut depth = 0;
           commit_str(&mut str, rest, &mut parts)?;
-          parts.extend(req.insert("++ std::string::convert (", ctx.source_range(0, rest)));
           let res = req.recurse(LexPluginRecur {
             tail: rest,
             exit: &mut |c| {
-              match c.chars().next() {
+              let mut chars = c.chars();
+              match chars.next() {
                 None => return Err(UnclosedInterpolation.pack(ctx.source_range(2, rest))),
-                Some('{') => depth += 1,
+                Some('{' | '(' | '[') => depth += 1,
                 Some('}') if depth == 0 => return Ok(true),
-                Some('}') => depth -= 1,
+                // a single colon introduces a format spec, unlike :: and :=
+                Some(':') if depth == 0 && !matches!(chars.next(), Some(':' | '=')) =>
+                  return Ok(true),
+                Some('}' | ')' | ']') => depth -= 1,
                 _ => (),
               }
               Ok(false)
             },
           })?;
-          txt = &res.tail[1..]; // account for final }
+          match res.tail.strip_prefix(':') {
+            None => {
+              txt = &res.tail[1..]; // account for final }
+              parts.extend(req.insert("++ std::string::convert (", ctx.source_range(0, rest)));
+            },
+            Some(spec_tail) => {
+              let spec_len = (spec_tail.find('}'))
+                .ok_or_else(|| UnclosedInterpolation.pack(ctx.source_range(2, rest)))?;
+              let (spec, close) = spec_tail.split_at(spec_len);
+              txt = &close[1..];
+              let spec = FormatSpec::parse(spec).unwrap_or_else(|e| {
+                ctx.reporter().report(BadFormatSpec(e).pack(ctx.source_range(spec.len(), close)));
+                FormatSpec::default()
+              });
+              let ag = AtomGenerator::cloner(Inert(spec));
+              parts.extend(req.insert("++ std::string::format_value", ctx.source_range(0, rest)));
+              parts.push(Entry::new(ctx.range(spec_len, close), Lexeme::Atom(ag)));
+              parts.extend(req.insert("(", ctx.source_range(0, rest)));
+            },
+          }
           parts.extend(res.tokens);
           parts.extend(req.insert(") ++", ctx.source_range(0, txt)));
         } else {
@@ -349,14 +456,19 @@ impl ParseErrorKind for BadEscapeSequence {
 mod test {
   use intern_all::i;
 
-  use super::StringLexer;
+  use super::{pad, parse_int, StringLexer};
   use crate::foreign::atom::Atomic;
   use crate::foreign::inert::Inert;
+  use crate::libs::std::format::{Align, FormatSpec};
+  use crate::libs::std::number::Numeric;
   use crate::libs::std::string::OrcString;
+  use crate::libs::std::test_utils::run;
+  use crate::location::{CodeGenInfo, CodeLocation};
   use crate::parse::context::MockContext;
   use crate::parse::lex_plugin::{LexPlugReqImpl, LexerPlugin};
   use crate::parse::lexer::Lexeme;
   use crate::parse::parsed::PType;
+  use crate::sym;
 
   #[test]
   fn plain_string() {
@@ -398,4 +510,63 @@ mod test {
     assert_eq!(res.tail, " - this dev");
     assert!(!ctx.0.failing(), "No errors were generated");
   }
+
+  #[test]
+  #[rustfmt::skip]
+  fn format_spec() {
+    let source = r#""${x::y:>4}|""#;
+    let ctx = MockContext::new();
+    let req = LexPlugReqImpl { ctx: &ctx, tail: source };
+    let res = (StringLexer.lex(&req))
+      .expect("the snippet starts with a quote")
+      .expect("it contains a valid string");
+    use Lexeme::{Name, LP, NS, RP};
+    let spec = FormatSpec { align: Some(Align::Right), width: 4, ..FormatSpec::default() };
+    let expected = [
+      LP(PType::Par),
+      Inert(OrcString::from("")).lexeme(),
+      Name(i!(str: "++")),
+      // std::string::format_value
+      Name(i!(str: "std")), NS, Name(i!(str: "string")), NS, Name(i!(str: "format_value")),
+      Inert(spec).lexeme(),
+      LP(PType::Par), Name(i!(str: "x")), NS, Name(i!(str: "y")), RP(PType::Par),
+      Name(i!(str: "++")),
+      Inert(OrcString::from("|")).lexeme(),
+      RP(PType::Par),
+    ];
+    assert_eq!(res.tokens, expected);
+    assert!(!ctx.0.failing(), "No errors were generated");
+  }
+
+  #[test]
+  fn bad_format_spec() {
+    let err = run("const main := \"${1:>4q}\"").unwrap_err();
+    assert!(err.contains("unknown format type 'q'"), "{err}");
+  }
+
+  #[test]
+  fn padding() {
+    assert_eq!(pad("42", 5, "0", false), "00042");
+    assert_eq!(pad("ab", 7, "-=", true), "ab-=-=-");
+    assert_eq!(pad("long", 2, "x", true), "long");
+  }
+
+  #[test]
+  fn radix_ints() {
+    let loc = CodeLocation::new_gen(CodeGenInfo::no_details(sym!(test)));
+    let parse = |s: &str, radix| parse_int(s, radix, loc.clone()).expect("radix is valid");
+    assert_eq!(parse("ff", 16), Some(Numeric::Uint(255)));
+    assert_eq!(parse("-0", 2), Some(Numeric::Uint(0)));
+    assert_eq!(parse("12a", 10), None);
+    assert_eq!(parse("++5", 10), None);
+    assert_eq!(parse("-+5", 10), None);
+    assert!(parse_int("-101", 2, loc.clone()).is_err());
+    assert!(parse_int("1", 37, loc).is_err());
+  }
+
+  #[test]
+  fn prefix_pattern() {
+    let src = "const main := match \"e\u{301}x\" { \"e\" ++ rest => rest; }";
+    assert_eq!(run(src).unwrap(), "\u{301}x");
+  }
 }
@@ -0,0 +1,32 @@
+//! Helpers for testing the standard library with Orchid code
+
+use super::std_system::StdConfig;
+use super::string::OrcString;
+use crate::error::Reporter;
+use crate::facade::loader::Loader;
+use crate::foreign::inert::Inert;
+use crate::gen::tpl;
+use crate::gen::traits::Gen;
+use crate::interpreter::gen_nort::nort_gen;
+use crate::location::{CodeGenInfo, CodeLocation};
+use crate::sym;
+use crate::virt_fs::{decl_file, DeclTree};
+
+/// Load `src` as `tree::main` with the standard library, and convert the
+/// value of its `main` constant to a string. Project and runtime errors are
+/// returned as their messages.
+pub fn run(src: &str) -> Result<String, String> {
+  let env = Loader::new().add_system(StdConfig { impure: true });
+  let reporter = Reporter::new();
+  let root = DeclTree::ns("tree::main", [decl_file(src)]);
+  let tree = env.load_project_main([sym!(tree::main::main)], root, &reporter);
+  let proc = env.proc(tree, true, Some(10_000), &reporter);
+  reporter.bind().map_err(|e| e.to_string())?;
+  let ctx = nort_gen(CodeLocation::new_gen(CodeGenInfo::no_details(sym!(test))));
+  let prompt = tpl::A(tpl::C("std::string::convert"), tpl::C("tree::main::main"));
+  let out = proc.run(prompt.template(ctx, []), Some(100_000)).map_err(|e| e.to_string())?;
+  match out.clone().downcast::<Inert<OrcString>>() {
+    Ok(s) => Ok(s.0.as_str().to_string()),
+    Err(_) => Err(format!("Did not normalize to a string: {out}")),
+  }
+}
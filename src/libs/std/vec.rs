@@ -0,0 +1,152 @@
+//! `std::vec` A persistent vector for random access to long sequences.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+
+use super::protocol::Tag;
+use super::reflect::refer;
+use crate::foreign::error::{AssertionError, RTResult};
+use crate::foreign::fn_bridge::Thunk;
+use crate::foreign::inert::{Inert, InertPayload};
+use crate::foreign::to_clause::{list, ToClause};
+use crate::foreign::try_from_expr::WithLoc;
+use crate::gen::tree::{atom_ent, xfn_ent, ConstTree};
+use crate::interpreter::nort::Expr;
+use crate::location::{CodeGenInfo, CodeLocation};
+use crate::sym;
+use crate::utils::ddispatch::Request;
+
+static VEC_TAG: Lazy<Tag> = Lazy::new(|| {
+  let location = CodeLocation::new_gen(CodeGenInfo::no_details(sym!(std::vec)));
+  Tag::new(sym!(std::vec), [(
+    sym!(std::string::conversion),
+    refer("std::vec::to_string_impl").into_expr(location),
+  )])
+});
+
+/// A persistent random access sequence of Orchid values. Copies share
+/// structure, so every operation is at most logarithmic in the length.
+#[derive(Clone)]
+pub struct OrcVec(pub im::Vector<Expr>);
+impl InertPayload for OrcVec {
+  const TYPE_STR: &'static str = "vec";
+  fn respond(&self, mut request: Request) { request.serve_with(|| VEC_TAG.clone()) }
+}
+impl fmt::Debug for OrcVec {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Vec")?;
+    f.debug_list().entries(self.0.iter().map(|e| &e.clause)).finish()
+  }
+}
+
+fn check_index(loc: CodeLocation, vec: &OrcVec, idx: usize) -> RTResult<()> {
+  match idx < vec.0.len() {
+    true => Ok(()),
+    false =>
+      AssertionError::fail(loc, "Vec index out of bounds", format!("{} <= {idx}", vec.0.len())),
+  }
+}
+
+fn length(vec: Inert<OrcVec>) -> Inert<usize> { Inert(vec.0.0.len()) }
+
+fn get(vec: Inert<OrcVec>, idx: Inert<usize>) -> Option<Expr> { vec.0.0.get(idx.0).cloned() }
+
+fn set(
+  WithLoc(loc, Inert(mut vec)): WithLoc<Inert<OrcVec>>,
+  idx: Inert<usize>,
+  item: Thunk,
+) -> RTResult<Inert<OrcVec>> {
+  check_index(loc, &vec, idx.0)?;
+  vec.0.set(idx.0, item.0);
+  Ok(Inert(vec))
+}
+
+fn push(Inert(mut vec): Inert<OrcVec>, item: Thunk) -> Inert<OrcVec> {
+  vec.0.push_back(item.0);
+  Inert(vec)
+}
+
+fn cons(item: Thunk, Inert(mut vec): Inert<OrcVec>) -> Inert<OrcVec> {
+  vec.0.push_front(item.0);
+  Inert(vec)
+}
+
+fn pop(Inert(mut vec): Inert<OrcVec>) -> Option<(Inert<OrcVec>, Expr)> {
+  let last = vec.0.pop_back()?;
+  Some((Inert(vec), last))
+}
+
+fn uncons(Inert(mut vec): Inert<OrcVec>) -> Option<(Expr, Inert<OrcVec>)> {
+  let head = vec.0.pop_front()?;
+  Some((head, Inert(vec)))
+}
+
+fn slice(
+  WithLoc(loc, vec): WithLoc<Inert<OrcVec>>,
+  i: Inert<usize>,
+  len: Inert<usize>,
+) -> RTResult<Inert<OrcVec>> {
+  let end = i.0.checked_add(len.0).filter(|end| *end <= vec.0.0.len());
+  let end = end.ok_or_else(|| {
+    let msg = format!("{} < {} + {}", vec.0.0.len(), i.0, len.0);
+    AssertionError::ext(loc, "Vec slice out of bounds", msg)
+  })?;
+  Ok(Inert(OrcVec(vec.0.0.clone().slice(i.0..end))))
+}
+
+fn concat(Inert(mut a): Inert<OrcVec>, b: Inert<OrcVec>) -> Inert<OrcVec> {
+  a.0.append(b.0.0);
+  Inert(a)
+}
+
+fn reverse(vec: Inert<OrcVec>) -> Inert<OrcVec> {
+  Inert(OrcVec(vec.0.0.iter().rev().cloned().collect()))
+}
+
+fn to_list(vec: Inert<OrcVec>) -> impl ToClause { list(vec.0.0) }
+
+pub(super) fn vec_lib() -> ConstTree {
+  ConstTree::ns("std::vec", [VEC_TAG.to_tree([
+    atom_ent("empty", [Inert(OrcVec(im::Vector::new()))]),
+    xfn_ent("length", [length]),
+    xfn_ent("get", [get]),
+    xfn_ent("set", [set]),
+    xfn_ent("push", [push]),
+    xfn_ent("cons", [cons]),
+    xfn_ent("pop", [pop]),
+    xfn_ent("uncons", [uncons]),
+    xfn_ent("slice", [slice]),
+    xfn_ent("concat", [concat]),
+    xfn_ent("reverse", [reverse]),
+    xfn_ent("to_list", [to_list]),
+  ])])
+}
+
+#[cfg(test)]
+mod test {
+  use crate::libs::std::test_utils::run;
+
+  #[test]
+  fn vec_ops() {
+    let run = |expr: &str| run(&format!("import std::vec\nconst main := {expr}"));
+    assert_eq!(run("vec::new[1, 2, 3]").unwrap(), "vec[1, 2, 3]");
+    assert_eq!(run("vec::set vec::new[1, 2, 3] 1 5").unwrap(), "vec[1, 5, 3]");
+    assert_eq!(run("vec::length (vec::cons 0 vec::new[1, 2])").unwrap(), "3");
+    assert_eq!(run("vec::slice vec::new[1, 2, 3, 4] 1 2").unwrap(), "vec[2, 3]");
+    let reversed = run("vec::reverse (vec::concat vec::new[1] vec::new[2, 3])");
+    assert_eq!(reversed.unwrap(), "vec[3, 2, 1]");
+    assert!(run("vec::get vec::new[1] 3").is_ok());
+    assert!(run("vec::set vec::new[1] 3 0").unwrap_err().contains("out of bounds"));
+  }
+
+  #[test]
+  fn vec_patterns() {
+    let src = "import std::vec
+      const main := match vec::new[1, 2, 3] {
+        vec::cons h (vec::push _ l) => h + l;
+      }";
+    assert_eq!(run(src).unwrap(), "4");
+    assert_eq!(run("const main := match std::vec::empty { std::vec::empty => 1; }").unwrap(), "1");
+  }
+}
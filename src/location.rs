@@ -50,6 +50,8 @@ pub struct SourceRange {
   pub(crate) range: Range<usize>,
 }
 impl SourceRange {
+  /// Create a new range in a source code unit
+  pub fn new(code: SourceCode, range: Range<usize>) -> Self { Self { code, range } }
   /// Create a dud [SourceRange] for testing. Its value is unspecified and
   /// volatile.
   pub fn mock() -> Self {
@@ -1,7 +1,10 @@
 //! Definition and implementations of the parsing context, which is used
 
+use std::any::{Any, TypeId};
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+use hashbrown::HashMap;
 
 use super::lex_plugin::LexerPlugin;
 use super::parse_plugin::ParseLinePlugin;
@@ -26,6 +29,9 @@ pub trait ParseCtx {
   /// Error reporter
   #[must_use]
   fn reporter(&self) -> &Reporter;
+  /// Values kept by parser plugins until the end of the load
+  #[must_use]
+  fn load_state(&self) -> LoadState;
   /// Find our position in the text given the text we've yet to parse
   #[must_use]
   fn pos(&self, tail: &str) -> usize {
@@ -64,6 +70,7 @@ pub trait ParseCtx {
 
 impl<'a, C: ParseCtx + 'a + ?Sized> ParseCtx for &'a C {
   fn reporter(&self) -> &Reporter { (*self).reporter() }
+  fn load_state(&self) -> LoadState { (*self).load_state() }
   fn lexers(&self) -> BoxedIter<'_, &dyn LexerPlugin> { (*self).lexers() }
   fn line_parsers(&self) -> BoxedIter<'_, &dyn ParseLinePlugin> { (*self).line_parsers() }
   fn pos(&self, tail: &str) -> usize { (*self).pos(tail) }
@@ -72,6 +79,20 @@ impl<'a, C: ParseCtx + 'a + ?Sized> ParseCtx for &'a C {
   fn range(&self, l: usize, t: &str) -> Range<usize> { (*self).range(l, t) }
 }
 
+/// Values that parser plugins keep while a project is loaded, such as
+/// declarations that the following files are checked against. There is one
+/// value of every type, created on first access.
+#[derive(Clone, Default)]
+pub struct LoadState(Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>>);
+impl LoadState {
+  /// Access the value of type `T`
+  pub fn with<T: Any + Default + Send, R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+    let mut values = self.0.lock().unwrap();
+    let value = values.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(T::default()));
+    f(value.downcast_mut().expect("Values are keyed by their type"))
+  }
+}
+
 /// Struct implementing context
 #[derive(Clone)]
 pub struct ParseCtxImpl<'a, 'b> {
@@ -83,9 +104,12 @@ pub struct ParseCtxImpl<'a, 'b> {
   pub lexers: Sequence<'a, &'a (dyn LexerPlugin + 'a)>,
   /// Parser plugins for parsing custom line structures
   pub line_parsers: Sequence<'a, &'a dyn ParseLinePlugin>,
+  /// State of the parser plugins shared by all files of the load
+  pub load_state: LoadState,
 }
 impl<'a, 'b> ParseCtx for ParseCtxImpl<'a, 'b> {
   fn reporter(&self) -> &Reporter { self.reporter }
+  fn load_state(&self) -> LoadState { self.load_state.clone() }
   // Rust doesn't realize that this lifetime is covariant
   #[allow(clippy::map_identity)]
   fn lexers(&self) -> BoxedIter<'_, &dyn LexerPlugin> { Box::new(self.lexers.iter().map(|r| r)) }
@@ -98,16 +122,17 @@ impl<'a, 'b> ParseCtx for ParseCtxImpl<'a, 'b> {
 
 /// Context instance for testing. Implicitly provides a reporter and panics if
 /// any errors are reported
-pub struct MockContext(pub Reporter);
+pub struct MockContext(pub Reporter, LoadState);
 impl MockContext {
   /// Create a new mock
-  pub fn new() -> Self { Self(Reporter::new()) }
+  pub fn new() -> Self { Self(Reporter::new(), LoadState::default()) }
 }
 impl Default for MockContext {
   fn default() -> Self { Self::new() }
 }
 impl ParseCtx for MockContext {
   fn reporter(&self) -> &Reporter { &self.0 }
+  fn load_state(&self) -> LoadState { self.1.clone() }
   fn pos(&self, tail: &str) -> usize { usize::MAX / 2 - tail.len() }
   // these are expendable
   fn code_info(&self) -> SourceCode { SourceRange::mock().code() }
@@ -132,6 +157,7 @@ impl<'a, C: ParseCtx + ?Sized> FlatLocContext<'a, C> {
 }
 impl<'a, C: ParseCtx + ?Sized> ParseCtx for FlatLocContext<'a, C> {
   fn reporter(&self) -> &Reporter { self.sub.reporter() }
+  fn load_state(&self) -> LoadState { self.sub.load_state() }
   fn pos(&self, _: &str) -> usize { 0 }
   fn lexers(&self) -> BoxedIter<'_, &dyn LexerPlugin> { self.sub.lexers() }
   fn line_parsers(&self) -> BoxedIter<'_, &dyn ParseLinePlugin> { self.sub.line_parsers() }
@@ -152,6 +178,7 @@ impl<'a, C: ParseCtx + ?Sized> ReporterContext<'a, C> {
 }
 impl<'a, C: ParseCtx + ?Sized> ParseCtx for ReporterContext<'a, C> {
   fn reporter(&self) -> &Reporter { self.reporter }
+  fn load_state(&self) -> LoadState { self.sub.load_state() }
   fn pos(&self, tail: &str) -> usize { self.sub.pos(tail) }
   fn lexers(&self) -> BoxedIter<'_, &dyn LexerPlugin> { self.sub.lexers() }
   fn line_parsers(&self) -> BoxedIter<'_, &dyn ParseLinePlugin> { self.sub.line_parsers() }
@@ -6,7 +6,7 @@ use itertools::Itertools;
 
 use super::context::ParseCtx;
 use super::frag::Frag;
-use super::lexer::{Entry, Lexeme};
+use super::lexer::{Entry, Lexeme, PRIORITY_BANDS};
 use crate::error::{ProjectError, ProjectErrorObj, ProjectResult};
 use crate::location::{CodeOrigin, SourceRange};
 use crate::parse::parsed::PType;
@@ -114,6 +114,12 @@ impl ParseErrorKind for ReservedToken {
   fn message(&self) -> String { format!("{} is a reserved token", self.0) }
 }
 
+/// A role annotation that doesn't follow a name or placeholder in a pattern
+pub(super) struct BadRoleTarget;
+impl ParseErrorKind for BadRoleTarget {
+  const DESCRIPTION: &'static str = "Only names and placeholders in patterns can have roles";
+}
+
 /// A token was found where it doesn't belong
 pub(super) struct BadTokenInRegion {
   /// What was found
@@ -164,6 +170,21 @@ impl ParseErrorKind for NoCommentEnd {
   const DESCRIPTION: &'static str = "a comment was not closed with `]--`";
 }
 
+/// A named priority that isn't in [PRIORITY_BANDS]
+pub(super) struct BadPriorityBand(pub String);
+impl ParseErrorKind for BadPriorityBand {
+  const DESCRIPTION: &'static str = "a named priority is not one of the priority bands";
+  fn message(&self) -> String {
+    let bands = (PRIORITY_BANDS.iter())
+      .map(|(name, _, width)| match width {
+        1 => format!("{name}(0)"),
+        _ => format!("{name}(0-{})", width - 1),
+      })
+      .join(", ");
+    format!("{} is not a valid priority. The bands are {bands}", self.0)
+  }
+}
+
 /// A placeholder's priority is a floating point number
 pub(super) struct FloatPlacehPrio;
 impl ParseErrorKind for FloatPlacehPrio {
@@ -13,7 +13,7 @@ use itertools::Itertools;
 use ordered_float::NotNan;
 
 use super::context::ParseCtx;
-use super::errors::{FloatPlacehPrio, NoCommentEnd};
+use super::errors::{BadPriorityBand, FloatPlacehPrio, NoCommentEnd};
 use super::lex_plugin::LexerPlugin;
 use super::numeric::{numstart, parse_num, print_nat16};
 use crate::foreign::atom::AtomGenerator;
@@ -56,7 +56,7 @@ pub enum Lexeme {
   Atom(AtomGenerator),
   /// Keyword or name
   Name(Tok<String>),
-  /// Macro operator `=`number`=>`
+  /// Macro operator `=`number`=>` or `=`band`(`n`)=>`
   Arrow(NotNan<f64>),
   /// `:=`
   Walrus,
@@ -86,7 +86,7 @@ impl fmt::Display for Lexeme {
       Self::Atom(a) => write!(f, "{a:?}"),
       Self::Name(token) => write!(f, "{}", **token),
       Self::Walrus => write!(f, ":="),
-      Self::Arrow(prio) => write!(f, "={}=>", print_nat16(*prio)),
+      Self::Arrow(prio) => write!(f, "={}=>", print_prio(*prio)),
       Self::NS => write!(f, "::"),
       Self::LP(t) => write!(f, "{}", t.l()),
       Self::RP(t) => write!(f, "{}", t.r()),
@@ -128,6 +128,36 @@ pub struct LexRes<'a> {
   pub tokens: Vec<Entry>,
 }
 
+/// Named bands of rule priorities, written as `=name(n)=>` in place of a
+/// number. The band `(name, start, width)` maps `name(n)` to `0x1p{start + n}`
+/// for `n < width`. See `notes/macros.md` for what each band is for.
+pub const PRIORITY_BANDS: &[(&str, i32, i32)] = &[
+  ("operator", 32, 8),
+  ("expression", 80, 8),
+  ("lambda", 128, 8),
+  ("alias", 200, 1),
+  ("integration", 224, 8),
+  ("transitional", 248, 8),
+];
+
+/// Resolve a named priority such as `lambda(1)`
+#[must_use]
+pub fn band_prio(band: &str, idx: i32) -> Option<NotNan<f64>> {
+  let (_, start, width) = PRIORITY_BANDS.iter().find(|(name, ..)| *name == band)?;
+  let prio = (0..*width).contains(&idx).then(|| 16f64.powi(start + idx))?;
+  Some(NotNan::new(prio).expect("Powers of 16 are not NaN"))
+}
+
+/// Print a rule priority by the name of its band if it's the exact value of
+/// one, otherwise as a base-16 number
+#[must_use]
+pub fn print_prio(prio: NotNan<f64>) -> String {
+  (PRIORITY_BANDS.iter())
+    .flat_map(|(name, _, width)| (0..*width).map(move |idx| (name, idx)))
+    .find(|(name, idx)| band_prio(name, *idx) == Some(prio))
+    .map_or_else(|| print_nat16(prio), |(name, idx)| format!("{name}({idx})"))
+}
+
 /// Neatly format source code
 #[allow(unused)]
 pub fn format(lexed: &[Entry]) -> String { lexed.iter().join(" ") }
@@ -235,6 +265,21 @@ pub fn lex<'a, E>(
     }
     // Parse a rule arrow
     if let Some(tail) = data.strip_prefix('=') {
+      let (band, post_band) = split_filter(tail, |c| c.is_ascii_lowercase());
+      if let Some(post_open) = post_band.strip_prefix('(').filter(|_| !band.is_empty()) {
+        let (idx, post_idx) = split_filter(post_open, |c| c.is_ascii_digit());
+        if let Some(tail) = post_idx.strip_prefix(")=>").filter(|_| !idx.is_empty()) {
+          let len = band.len() + idx.len() + 5;
+          let prio = idx.parse().ok().and_then(|idx| band_prio(band, idx)).unwrap_or_else(|| {
+            let err = BadPriorityBand(format!("{band}({idx})"));
+            ctx.reporter().report(err.pack(ctx.source_range(len, tail)));
+            NotNan::new(0.0).expect("Not NaN")
+          });
+          tokens.push(Entry::new(ctx.range(len, tail), Lexeme::Arrow(prio)));
+          data = tail;
+          continue 'tail;
+        }
+      }
       if tail.chars().next().map_or(false, numstart) {
         let (num, post_num) = split_filter(tail, numchar);
         if let Some(tail) = post_num.strip_prefix("=>") {
@@ -316,3 +361,18 @@ pub fn lex<'a, E>(
     unreachable!(r#"opchar is pretty much defined as "not namechar" "#)
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{band_prio, print_prio};
+  use crate::parse::numeric::parse_num;
+
+  #[test]
+  fn test_priority_bands() {
+    let prio = |s| parse_num(s).unwrap().as_float();
+    assert_eq!(band_prio("lambda", 1), Some(prio("0x1p129")));
+    assert_eq!(band_prio("alias", 1), None);
+    assert_eq!(print_prio(prio("0x1p230")), "integration(6)");
+    assert_eq!(print_prio(prio("0x2p230")), "0x2p230");
+  }
+}
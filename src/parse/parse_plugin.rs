@@ -7,7 +7,7 @@ use std::ops::Range;
 use dyn_clone::DynClone;
 use intern_all::Tok;
 
-use super::context::ParseCtx;
+use super::context::{LoadState, ParseCtx};
 use super::errors::{expect, expect_block, expect_name};
 use super::facade::parse_entries;
 use super::frag::Frag;
@@ -80,6 +80,8 @@ pub trait ParsePluginReq<'t> {
   /// Report a fatal error while also producing output to be consumed by later
   /// stages for improved error reporting
   fn report_err(&self, e: ProjectErrorObj);
+  /// Values kept until the end of the load, see [LoadState]
+  fn load_state(&self) -> LoadState;
 }
 
 /// External plugin that parses an unrecognized source line into lines of
@@ -139,4 +141,5 @@ impl<'ty, TCtx: ParseCtx + ?Sized> ParsePluginReq<'ty> for ParsePlugReqImpl<'ty,
   }
   fn expect_empty(&self, f: Frag) -> ProjectResult<()> { f.expect_empty(self.ctx) }
   fn report_err(&self, e: ProjectErrorObj) { self.ctx.reporter().report(e) }
+  fn load_state(&self) -> LoadState { self.ctx.load_state() }
 }
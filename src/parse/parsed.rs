@@ -5,7 +5,7 @@
 
 use std::fmt;
 use std::hash::Hash;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use hashbrown::HashSet;
 use intern_all::Tok;
@@ -17,7 +17,7 @@ use crate::foreign::atom::AtomGenerator;
 use crate::interpreter::nort;
 use crate::location::SourceRange;
 use crate::name::{Sym, VName, VPath};
-use crate::parse::numeric::print_nat16;
+use crate::parse::lexer::print_prio;
 
 /// A [Clause] with associated metadata
 #[derive(Clone, Debug)]
@@ -140,9 +140,9 @@ pub enum Clause {
   Name(Sym),
   /// A parenthesized expression
   /// eg. `(print out "hello")`, `[1, 2, 3]`, `{Some(t) => t}`
-  S(PType, Rc<Vec<Expr>>),
+  S(PType, Arc<Vec<Expr>>),
   /// A function expression, eg. `\x. x + 1`
-  Lambda(Rc<Vec<Expr>>, Rc<Vec<Expr>>),
+  Lambda(Arc<Vec<Expr>>, Arc<Vec<Expr>>),
   /// A placeholder for macros, eg. `$name`, `...$body`, `...$lhs:1`
   Placeh(Placeholder),
 }
@@ -150,7 +150,7 @@ pub enum Clause {
 impl Clause {
   /// Extract the expressions from an auto, lambda or S
   #[must_use]
-  pub fn body(&self) -> Option<Rc<Vec<Expr>>> {
+  pub fn body(&self) -> Option<Arc<Vec<Expr>>> {
     match self {
       Self::Lambda(_, body) | Self::S(_, body) => Some(body.clone()),
       _ => None,
@@ -174,13 +174,13 @@ impl Clause {
     match exprs {
       [] => None,
       [only] => Some(only.value.clone()),
-      _ => Some(Self::S(PType::Par, Rc::new(exprs.to_vec()))),
+      _ => Some(Self::S(PType::Par, Arc::new(exprs.to_vec()))),
     }
   }
 
   /// Convert with identical meaning
   #[must_use]
-  pub fn from_exprv(exprv: &Rc<Vec<Expr>>) -> Option<Clause> {
+  pub fn from_exprv(exprv: &Arc<Vec<Expr>>) -> Option<Clause> {
     if exprv.len() < 2 { Self::from_exprs(exprv) } else { Some(Self::S(PType::Par, exprv.clone())) }
   }
 
@@ -220,7 +220,7 @@ impl Clause {
             val.unwrap_or_else(|| e.clone())
           })
           .collect();
-        if any_some { Some(Clause::S(*c, Rc::new(new_body))) } else { None }
+        if any_some { Some(Clause::S(*c, Arc::new(new_body))) } else { None }
       },
       Clause::Lambda(arg, body) => {
         let mut any_some = false;
@@ -238,7 +238,7 @@ impl Clause {
             val.unwrap_or_else(|| e.clone())
           })
           .collect();
-        if any_some { Some(Clause::Lambda(Rc::new(new_arg), Rc::new(new_body))) } else { None }
+        if any_some { Some(Clause::Lambda(Arc::new(new_arg), Arc::new(new_body))) } else { None }
       },
     }
   }
@@ -271,7 +271,7 @@ impl Clause {
       _ => panic!("not an opening paren"),
     };
     let body = body.into_iter().map(|it| it.into_expr(range.clone())).collect();
-    Self::S(ptype, Rc::new(body))
+    Self::S(ptype, Arc::new(body))
   }
 }
 
@@ -319,6 +319,9 @@ pub struct Rule {
   pub prio: NotNan<f64>,
   /// Expressions on the right side of the arrow
   pub template: Vec<Expr>,
+  /// Roles of names and placeholders in the pattern, written as `token@role`
+  /// and identified by the location of the token
+  pub roles: Vec<(SourceRange, Tok<String>)>,
 }
 
 impl fmt::Display for Rule {
@@ -327,7 +330,7 @@ impl fmt::Display for Rule {
       f,
       "rule {} ={}=> {}",
       self.pattern.iter().join(" "),
-      print_nat16(self.prio),
+      print_prio(self.prio),
       self.template.iter().join(" ")
     )
   }
@@ -340,6 +343,9 @@ pub struct Constant {
   pub name: Tok<String>,
   /// The constant value inserted where the name is found
   pub value: Expr,
+  /// Whether macros are applied to the value. Line plugins can turn this off
+  /// to keep a value as written.
+  pub expand: bool,
 }
 
 impl fmt::Display for Constant {
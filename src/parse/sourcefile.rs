@@ -1,15 +1,17 @@
 //! Internal states of the parser.
 
 use std::iter;
-use std::rc::Rc;
+use std::ops::Range;
+use std::sync::Arc;
 
-use intern_all::i;
+use intern_all::{i, Tok};
 use itertools::Itertools;
 
 use super::context::ParseCtx;
 use super::errors::{
-  expect, expect_block, expect_name, BadTokenInRegion, ExpectedSingleName, GlobExport, LeadingNS,
-  MisalignedParen, NamespacedExport, ParseErrorKind, ReservedToken, UnexpectedEOL,
+  expect, expect_block, expect_name, BadRoleTarget, BadTokenInRegion, ExpectedSingleName,
+  GlobExport, LeadingNS, MisalignedParen, NamespacedExport, ParseErrorKind, ReservedToken,
+  UnexpectedEOL,
 };
 use super::frag::Frag;
 use super::lexer::{Entry, Lexeme};
@@ -164,15 +166,56 @@ fn parse_member(cursor: Frag<'_>, ctx: &(impl ParseCtx + ?Sized)) -> ProjectResu
   }
 }
 
+/// A role annotation with the end of the token it belongs to
+type RoleMark = (usize, Tok<String>, Range<usize>);
+
+/// Remove the `@role` annotations from a pattern
+fn strip_roles(
+  pattern: Frag<'_>,
+  ctx: &(impl ParseCtx + ?Sized),
+) -> ProjectResult<(Vec<Entry>, Vec<RoleMark>)> {
+  let mut tokens = Vec::<Entry>::new();
+  let mut marks = Vec::new();
+  let mut data = pattern.data.iter();
+  while let Some(entry) = data.next() {
+    if !entry.lexeme.strict_eq(&Lexeme::At) {
+      tokens.push(entry.clone());
+      continue;
+    }
+    let name_ent = (data.next())
+      .ok_or_else(|| UnexpectedEOL(Lexeme::At).pack(ctx.range_loc(&entry.range)))?;
+    let role = expect_name(name_ent, ctx)?;
+    let range = entry.range.start..name_ent.range.end;
+    match tokens.last().filter(|e| !e.is_filler()) {
+      None => return Err(BadRoleTarget.pack(ctx.range_loc(&range))),
+      Some(target) => marks.push((target.range.end, role, range)),
+    }
+  }
+  Ok((tokens, marks))
+}
+
 /// Parse a macro rule
 pub fn parse_rule(cursor: Frag<'_>, ctx: &(impl ParseCtx + ?Sized)) -> ProjectResult<Rule> {
   let (pattern, prio, template) = cursor.find_map("arrow", ctx, |a| match a {
     Lexeme::Arrow(p) => Some(*p),
     _ => None,
   })?;
-  let (pattern, _) = parse_exprv(pattern, None, ctx)?;
+  let (tokens, marks) = strip_roles(pattern, ctx)?;
+  let (pattern, _) = parse_exprv(Frag::new(pattern.fallback, &tokens), None, ctx)?;
   let (template, _) = parse_exprv(template, None, ctx)?;
-  Ok(Rule { pattern, prio, template })
+  let roles = (marks.into_iter())
+    .map(|(end, role, range)| {
+      let target = pattern.iter().find_map(|e| {
+        e.search_all(&mut |e| match &e.value {
+          Clause::Name(_) | Clause::Placeh(_) if e.range.range().end == end =>
+            Some(e.range.clone()),
+          _ => None,
+        })
+      });
+      target.map(|t| (t, role)).ok_or_else(|| BadRoleTarget.pack(ctx.range_loc(&range)))
+    })
+    .collect::<ProjectResult<Vec<_>>>()?;
+  Ok(Rule { pattern, prio, template, roles })
 }
 
 /// Parse a constant declaration
@@ -185,7 +228,7 @@ pub fn parse_const(cursor: Frag<'_>, ctx: &(impl ParseCtx + ?Sized)) -> ProjectR
     parse_exprv(cursor, None, ctx).and_then(|(body, _)| exprv_to_single(walrus_ent, body, ctx)),
     |_| Clause::Name(sym!(__syntax_error__)).into_expr(ctx.range_loc(&cursor.range())),
   );
-  Ok(Constant { name, value })
+  Ok(Constant { name, value, expand: true })
 }
 
 /// Parse a namespaced name. TODO: use this for modules
@@ -264,7 +307,7 @@ pub fn parse_exprv<'a>(
       Lexeme::LP(c) => {
         let (result, leftover) = parse_exprv(cursor.step(ctx)?, Some(*c), ctx)?;
         let range = current.range.start..leftover.fallback.range.end;
-        let value = Clause::S(*c, Rc::new(result));
+        let value = Clause::S(*c, Arc::new(result));
         output.push(Expr { value, range: ctx.range_loc(&range) });
         cursor = leftover;
       },
@@ -276,7 +319,7 @@ pub fn parse_exprv<'a>(
         let (body, leftover) = parse_exprv(body, paren, ctx)?;
         output.push(Expr {
           range: ctx.range_loc(&cursor.range()),
-          value: Clause::Lambda(Rc::new(arg), Rc::new(body)),
+          value: Clause::Lambda(Arc::new(arg), Arc::new(body)),
         });
         return Ok((output, leftover));
       },
@@ -307,7 +350,7 @@ pub fn exprv_to_single(
       let f_range = &v.first().unwrap().range;
       let l_range = &v.last().unwrap().range;
       let range = f_range.map_range(|r| r.start..l_range.range.end);
-      Ok(Expr { range, value: Clause::S(PType::Par, Rc::new(v)) })
+      Ok(Expr { range, value: Clause::S(PType::Par, Arc::new(v)) })
     },
   }
 }
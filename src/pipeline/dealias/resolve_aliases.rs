@@ -95,11 +95,12 @@ fn resolve_aliases_rec(
       src: module.x.src.as_ref().map(|s| SourceModule {
         range: s.range.clone(),
         rules: (s.rules.iter())
-          .map(|ProjRule { pattern, prio, template, comments }| ProjRule {
+          .map(|ProjRule { pattern, prio, template, comments, roles }| ProjRule {
             pattern: pattern.iter().map(|e| process_expr(e, root, path, env, reporter)).collect(),
             template: template.iter().map(|e| process_expr(e, root, path, env, reporter)).collect(),
             comments: comments.clone(),
             prio: *prio,
+            roles: roles.clone(),
           })
           .collect(),
       }),
@@ -111,6 +112,7 @@ fn resolve_aliases_rec(
             exported: v.x.exported,
             comments: v.x.comments.clone(),
             locations: v.x.locations.clone(),
+            expand: v.x.expand,
           },
           member: match &v.member {
             ModMember::Sub(module) => {
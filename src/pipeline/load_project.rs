@@ -12,7 +12,7 @@ use super::project::{ItemKind, ProjItem, ProjXEnt, ProjectMod, ProjectTree};
 use crate::error::{ErrorPosition, ProjectError, Reporter};
 use crate::location::{CodeGenInfo, CodeOrigin, SourceCode, SourceRange};
 use crate::name::{NameLike, PathSlice, Sym, VName, VPath};
-use crate::parse::context::ParseCtxImpl;
+use crate::parse::context::{LoadState, ParseCtxImpl};
 use crate::parse::facade::parse_file;
 use crate::parse::lex_plugin::LexerPlugin;
 use crate::parse::parse_plugin::ParseLinePlugin;
@@ -69,6 +69,8 @@ pub struct ProjectContext<'a, 'b> {
   pub preludes: Sequence<'a, &'a Prelude>,
   /// Error aggregator
   pub reporter: &'b Reporter,
+  /// State of the parser plugins for this load
+  pub load_state: LoadState,
 }
 impl<'a, 'b> ProjectContext<'a, 'b> {
   /// Derive context for the parser
@@ -78,6 +80,7 @@ impl<'a, 'b> ProjectContext<'a, 'b> {
       reporter: self.reporter,
       lexers: self.lexer_plugins.clone(),
       line_parsers: self.line_parsers.clone(),
+      load_state: self.load_state.clone(),
     }
   }
 }
@@ -61,7 +61,7 @@ pub(super) struct FileReport {
 fn default_entry() -> ProjectEntry {
   ProjectEntry {
     member: ModMember::Item(ProjItem::default()),
-    x: ProjXEnt { comments: vec![], locations: vec![], exported: false },
+    x: ProjXEnt { comments: vec![], locations: vec![], exported: false, expand: true },
   }
 }
 
@@ -124,7 +124,7 @@ pub(super) fn process_ns(
         }
       },
       SourceLineKind::Member(Member { exported, kind }) => match kind {
-        MemberKind::Constant(Constant { name, value }) => {
+        MemberKind::Constant(Constant { name, value, expand }) => {
           let entry = get_or_make(&mut entries, &name, default_entry);
           entry.x.locations.push(CodeLocation::new_src(range, path.clone()));
           if let ModMember::Item(ProjItem { kind: old @ ItemKind::None }) = &mut entry.member {
@@ -133,10 +133,11 @@ pub(super) fn process_ns(
             reporter.report(MultipleDefinitions::new(path.clone(), name.clone(), entry).pack());
           }
           entry.x.exported |= exported;
+          entry.x.expand = expand;
           entry.x.comments.append(&mut new_comments);
         },
-        MemberKind::Rule(Rule { pattern, prio, template }) => {
-          let prule = ProjRule { pattern, prio, template, comments: new_comments };
+        MemberKind::Rule(Rule { pattern, prio, template, roles }) => {
+          let prule = ProjRule { pattern, prio, template, comments: new_comments, roles };
           new_comments = Vec::new();
           for name in prule.collect_root_names() {
             let entry = get_or_make(&mut entries, &name, default_entry);
@@ -11,7 +11,7 @@ use ordered_float::NotNan;
 
 use crate::location::{CodeLocation, CodeOrigin, SourceRange};
 use crate::name::{Sym, VPath};
-use crate::parse::numeric::print_nat16;
+use crate::parse::lexer::print_prio;
 use crate::parse::parsed::{Clause, Expr};
 use crate::tree::{ModEntry, ModMember, ModMemberRef, Module, TreeTransforms};
 use crate::utils::combine::Combine;
@@ -71,15 +71,17 @@ pub struct ProjRule {
   pub template: Vec<Expr>,
   /// Comments associated with this rule
   pub comments: Vec<Arc<String>>,
+  /// Roles of tokens in the pattern, identified by their location
+  pub roles: Vec<(SourceRange, Tok<String>)>,
 }
 
 impl ProjRule {
   /// Namespace all tokens in the rule
   #[must_use]
   pub fn prefix(self, prefix: &[Tok<String>], except: &impl Fn(Tok<String>) -> bool) -> Self {
-    let Self { comments, prio, mut pattern, mut template } = self;
+    let Self { comments, prio, mut pattern, mut template, roles } = self;
     (pattern.iter_mut()).chain(template.iter_mut()).for_each(|e| *e = e.prefix(prefix, except));
-    Self { prio, comments, pattern, template }
+    Self { prio, comments, pattern, template, roles }
   }
 
   /// Return a list of all names that don't contain a namespace separator `::`.
@@ -106,7 +108,7 @@ impl fmt::Display for ProjRule {
       "{}rule {} ={}=> {}",
       self.comments.iter().map(|s| format!("--[{s}]--\n")).join(""),
       self.pattern.iter().join(" "),
-      print_nat16(self.prio),
+      print_prio(self.prio),
       self.template.iter().join(" ")
     )
   }
@@ -148,9 +150,14 @@ pub struct ProjXEnt {
   pub exported: bool,
   /// Location of this item
   pub locations: Vec<CodeLocation>,
+  /// Whether macros are applied to the constant, see
+  /// [crate::parse::parsed::Constant::expand]
+  pub expand: bool,
 }
 impl Default for ProjXEnt {
-  fn default() -> Self { Self { comments: vec![], exported: true, locations: vec![] } }
+  fn default() -> Self {
+    Self { comments: vec![], exported: true, locations: vec![], expand: true }
+  }
 }
 impl ProjXEnt {
   /// Implied modules can be merged easily. It's difficult to detect whether
@@ -0,0 +1,99 @@
+//! Detection of macro expansions that return to an earlier state and would
+//! therefore never halt.
+
+use std::slice;
+use std::sync::Arc;
+
+use super::matcher::RuleExpr;
+use crate::parse::parsed::Clause;
+
+/// Longest cycle that is detected, in steps. Longer ones run into the step
+/// limit instead.
+pub(super) const MAX_PERIOD: usize = 256;
+
+/// Whether two sequences have the same structure. Locations are ignored, and
+/// subtrees shared by both sides are skipped, so comparing an expression with
+/// an earlier state of itself only visits the parts that were rewritten.
+fn same_exprv(a: &[RuleExpr], b: &[RuleExpr]) -> bool {
+  type Pending<'a> = Vec<(&'a [RuleExpr], &'a [RuleExpr])>;
+  fn push<'a>(pending: &mut Pending<'a>, a: &'a Arc<Vec<RuleExpr>>, b: &'a Arc<Vec<RuleExpr>>) {
+    if !Arc::ptr_eq(a, b) {
+      pending.push((a, b))
+    }
+  }
+  let mut pending = vec![(a, b)];
+  while let Some((a, b)) = pending.pop() {
+    if a.len() != b.len() {
+      return false;
+    }
+    for (a, b) in a.iter().zip(b) {
+      match (&a.value, &b.value) {
+        (Clause::S(ap, ab), Clause::S(bp, bb)) if ap == bp => push(&mut pending, ab, bb),
+        (Clause::Lambda(aa, ab), Clause::Lambda(ba, bb)) => {
+          push(&mut pending, aa, ba);
+          push(&mut pending, ab, bb)
+        },
+        (Clause::Name(an), Clause::Name(bn)) if an == bn => (),
+        (Clause::Placeh(aph), Clause::Placeh(bph)) if aph == bph => (),
+        (Clause::Atom(aa), Clause::Atom(ba)) if aa.identity() == ba.identity() || aa == ba => (),
+        _ => return false,
+      }
+    }
+  }
+  true
+}
+
+/// Compares each state of an expression with a checkpoint that is moved
+/// forward at exponentially growing intervals up to [MAX_PERIOD] (Brent's
+/// algorithm), so only one earlier state is kept.
+pub(super) struct CycleDetector {
+  checkpoint: RuleExpr,
+  checkpoint_step: usize,
+  interval: usize,
+}
+impl CycleDetector {
+  /// Start from the initial state of the expression
+  pub fn new(expr: &RuleExpr) -> Self {
+    Self { checkpoint: expr.clone(), checkpoint_step: 0, interval: 1 }
+  }
+
+  /// Record the state after step `step`. If it is equal to the checkpoint,
+  /// return the step that produced the checkpoint.
+  pub fn visit(&mut self, expr: &RuleExpr, step: usize) -> Option<usize> {
+    if same_exprv(slice::from_ref(expr), slice::from_ref(&self.checkpoint)) {
+      return Some(self.checkpoint_step);
+    }
+    if self.interval <= step - self.checkpoint_step {
+      self.checkpoint = expr.clone();
+      self.checkpoint_step = step;
+      self.interval = (self.interval * 2).min(MAX_PERIOD);
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use super::CycleDetector;
+  use crate::location::SourceRange;
+  use crate::name::Sym;
+  use crate::parse::parsed::{Clause, PType};
+  use crate::sym;
+
+  #[test]
+  fn test_cycle_detector() {
+    let seq = |names: &[Sym], range: SourceRange| {
+      let body = names.iter().map(|n| Clause::Name(n.clone()).into_expr(range.clone())).collect();
+      Clause::S(PType::Par, Arc::new(body)).into_expr(range)
+    };
+    let (a, b) = (sym!(test::a), sym!(test::b));
+    let mut states = CycleDetector::new(&seq(&[a.clone(), b.clone()], SourceRange::mock()));
+    assert_eq!(states.visit(&seq(&[b.clone(), a.clone()], SourceRange::mock()), 1), None);
+    assert_eq!(states.visit(&seq(&[b.clone(), b.clone()], SourceRange::mock()), 2), None);
+    // equal to the checkpoint taken at step 1, in spite of the location
+    let other_loc = SourceRange::mock().map_range(|_| 1..2);
+    assert_eq!(states.visit(&seq(&[b, a], other_loc), 3), Some(1));
+  }
+}
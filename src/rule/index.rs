@@ -0,0 +1,221 @@
+//! Structures that narrow down the rules worth trying on an expression, so
+//! that the repository doesn't have to test every rule on every step.
+
+use std::hash::Hash;
+
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+use super::matcher::RuleExpr;
+use crate::name::Sym;
+use crate::parse::parsed::{Clause, PType};
+
+fn add_count<K: Eq + Hash>(map: &mut HashMap<K, usize>, key: K) {
+  *map.entry(key).or_insert(0) += 1
+}
+
+fn sub_count<K: Eq + Hash>(map: &mut HashMap<K, usize>, key: K) {
+  let count = map.get_mut(&key).expect("Removed an element that was never added");
+  *count -= 1;
+  if *count == 0 {
+    map.remove(&key);
+  }
+}
+
+/// Visit every name and bracket in a sequence, including nested ones. The
+/// tree is walked with an explicit stack because macros can nest expressions
+/// arbitrarily deep.
+fn walk(exprv: &[RuleExpr], mut name: impl FnMut(&Sym), mut bracket: impl FnMut(PType)) {
+  let mut stack = exprv.iter().collect_vec();
+  while let Some(expr) = stack.pop() {
+    match &expr.value {
+      Clause::Name(n) => name(n),
+      Clause::S(p, body) => {
+        bracket(*p);
+        stack.extend(body.iter())
+      },
+      Clause::Lambda(arg, body) => stack.extend(arg.iter().chain(body.iter())),
+      Clause::Atom(_) | Clause::Placeh(_) => (),
+    }
+  }
+}
+
+/// Multiset of the names and bracket shapes in an expression. Substitutions
+/// update it by removing the replaced sequence and adding its replacement, so
+/// it never has to be recomputed from the whole tree.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Glossary {
+  names: HashMap<Sym, usize>,
+  brackets: HashMap<PType, usize>,
+}
+impl Glossary {
+  /// Collect the names and brackets of a sequence
+  pub fn new(exprv: &[RuleExpr]) -> Self {
+    let mut glossary = Self::default();
+    glossary.add(exprv);
+    glossary
+  }
+
+  /// Register a sequence that was inserted into the expression
+  pub fn add(&mut self, exprv: &[RuleExpr]) {
+    let Self { names, brackets } = self;
+    walk(exprv, |n| add_count(names, n.clone()), |p| add_count(brackets, p))
+  }
+
+  /// Unregister a sequence that was removed from the expression
+  pub fn remove(&mut self, exprv: &[RuleExpr]) {
+    let Self { names, brackets } = self;
+    walk(exprv, |n| sub_count(names, n.clone()), |p| sub_count(brackets, p))
+  }
+}
+
+/// Names and brackets a pattern consists of. Code that lacks any of them can't
+/// be matched by the pattern.
+#[derive(Clone, Debug)]
+pub(super) struct Shape {
+  /// Every name in the pattern
+  pub names: HashSet<Sym>,
+  brackets: HashSet<PType>,
+  /// Names outside brackets, which must appear in the matched sequence itself
+  top_names: Vec<Sym>,
+  /// Brackets outside brackets, which must appear in the matched sequence
+  top_brackets: Vec<PType>,
+}
+impl Shape {
+  pub fn new(pattern: &[RuleExpr]) -> Self {
+    let Glossary { names, brackets } = Glossary::new(pattern);
+    let top_names = (pattern.iter())
+      .filter_map(|e| if let Clause::Name(n) = &e.value { Some(n.clone()) } else { None })
+      .collect();
+    let top_brackets =
+      (pattern.iter()).filter_map(|e| if let Clause::S(p, _) = &e.value { Some(*p) } else { None });
+    Self {
+      names: names.into_keys().collect(),
+      brackets: brackets.into_keys().collect(),
+      top_names,
+      top_brackets: top_brackets.collect(),
+    }
+  }
+
+  /// Whether an expression with this glossary may contain a match
+  pub fn admits(&self, glossary: &Glossary) -> bool {
+    self.names.iter().all(|n| glossary.names.contains_key(n))
+      && self.brackets.iter().all(|p| glossary.brackets.contains_key(p))
+  }
+
+  /// Whether this sequence may be matched
+  pub fn may_match(&self, exprv: &[RuleExpr]) -> bool {
+    let has_name = |n: &Sym| exprv.iter().any(|e| matches!(&e.value, Clause::Name(m) if m == n));
+    let has_bracket = |p: PType| exprv.iter().any(|e| matches!(e.value, Clause::S(q, _) if q == p));
+    self.top_names.iter().all(has_name) && self.top_brackets.iter().all(|p| has_bracket(*p))
+  }
+}
+
+/// Discrimination index over rules. Every rule is filed under the name in its
+/// pattern that the fewest other patterns contain, so only the rules filed
+/// under a name in the glossary need to be considered.
+#[derive(Clone, Debug, Default)]
+pub(super) struct RuleIndex {
+  by_key: HashMap<Sym, Vec<usize>>,
+  /// Rules whose pattern contains no names
+  unkeyed: Vec<usize>,
+}
+impl RuleIndex {
+  /// Index the shapes of a list of rules. The returned candidates refer to
+  /// positions in this list.
+  pub fn new<'a>(shapes: impl IntoIterator<Item = &'a Shape> + Clone) -> Self {
+    let mut frequency = HashMap::new();
+    for name in shapes.clone().into_iter().flat_map(|s| s.names.iter()) {
+      add_count(&mut frequency, name);
+    }
+    let mut index = Self::default();
+    for (i, shape) in shapes.into_iter().enumerate() {
+      match shape.names.iter().min_by_key(|n| (frequency[n], n.id())) {
+        None => index.unkeyed.push(i),
+        Some(key) => index.by_key.entry(key.clone()).or_default().push(i),
+      }
+    }
+    index
+  }
+
+  /// Positions of the rules that may match an expression with this glossary,
+  /// in ascending order
+  pub fn candidates(&self, glossary: &Glossary) -> Vec<usize> {
+    let mut candidates = self.unkeyed.clone();
+    if glossary.names.len() < self.by_key.len() {
+      let buckets = glossary.names.keys().filter_map(|n| self.by_key.get(n));
+      candidates.extend(buckets.flatten());
+    } else {
+      let buckets = self.by_key.iter().filter(|(n, _)| glossary.names.contains_key(*n));
+      candidates.extend(buckets.flat_map(|(_, v)| v));
+    }
+    candidates.sort_unstable();
+    candidates
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use super::{Glossary, RuleIndex, Shape};
+  use crate::location::SourceRange;
+  use crate::name::Sym;
+  use crate::parse::parsed::{Clause, PType};
+  use crate::rule::matcher::RuleExpr;
+  use crate::sym;
+
+  fn name(n: &Sym) -> RuleExpr { Clause::Name(n.clone()).into_expr(SourceRange::mock()) }
+  fn group(p: PType, body: Vec<RuleExpr>) -> RuleExpr {
+    Clause::S(p, Arc::new(body)).into_expr(SourceRange::mock())
+  }
+
+  /// Every combination of up to two of the names, once at the top and once
+  /// in square brackets
+  fn sequences(names: &[Sym]) -> Vec<Vec<RuleExpr>> {
+    let mut out = vec![vec![]];
+    for (i, a) in names.iter().enumerate() {
+      out.push(vec![name(a)]);
+      for b in &names[i..] {
+        out.push(vec![name(a), name(b)]);
+        out.push(vec![name(a), group(PType::Sqr, vec![name(b)])]);
+      }
+    }
+    out
+  }
+
+  #[test]
+  fn candidates_include_admitted_rules() {
+    let names = [sym!(test::a), sym!(test::b), sym!(test::c), sym!(test::d)];
+    let shapes = sequences(&names[..3]).iter().map(|p| Shape::new(p)).collect::<Vec<_>>();
+    let index = RuleIndex::new(&shapes);
+    for code in sequences(&names) {
+      let glossary = Glossary::new(&code);
+      let candidates = index.candidates(&glossary);
+      assert!(candidates.windows(2).all(|w| w[0] < w[1]), "in priority order without repeats");
+      for (i, shape) in shapes.iter().enumerate() {
+        if shape.admits(&glossary) {
+          assert!(candidates.contains(&i), "rule {i} can match but isn't a candidate");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn incremental_glossary() {
+    let names = [sym!(test::a), sym!(test::b), sym!(test::c)];
+    let seqs = sequences(&names);
+    for removed in &seqs {
+      for added in &seqs {
+        let before = [name(&names[0]), group(PType::Curl, removed.clone())];
+        let after = [name(&names[0]), group(PType::Curl, added.clone())];
+        let mut glossary = Glossary::new(&before);
+        glossary.remove(removed);
+        glossary.add(added);
+        let rebuilt = Glossary::new(&after);
+        assert_eq!(glossary.names, rebuilt.names);
+        assert_eq!(glossary.brackets, rebuilt.brackets);
+      }
+    }
+  }
+}
@@ -1,7 +1,7 @@
 //! Abstract definition of a rule matcher, so that the implementation can
 //! eventually be swapped out for a different one.
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::state::State;
 use crate::name::Sym;
@@ -15,7 +15,7 @@ pub type RuleExpr = Expr;
 pub trait Matcher {
   /// Build matcher for a pattern
   #[must_use]
-  fn new(pattern: Rc<Vec<RuleExpr>>) -> Self;
+  fn new(pattern: Arc<Vec<RuleExpr>>) -> Self;
   /// Apply matcher to a token sequence
   #[must_use]
   fn apply<'a>(&self, source: &'a [RuleExpr], save_loc: &impl Fn(Sym) -> bool)
@@ -106,7 +106,7 @@ fn mk_scalar(pattern: &RuleExpr) -> ScalMatcher {
 
 #[cfg(test)]
 mod test {
-  use std::rc::Rc;
+  use std::sync::Arc;
 
   use intern_all::i;
 
@@ -126,7 +126,7 @@ mod test {
       ex(Clause::Name(sym!(prelude::do))),
       ex(Clause::S(
         PType::Par,
-        Rc::new(vec![
+        Arc::new(vec![
           ex(Clause::Placeh(Placeholder {
             class: PHClass::Vec { nonzero: false, prio: 0 },
             name: i!(str: "expr"),
@@ -16,6 +16,7 @@
 
 mod any_match;
 mod build;
+mod overlap;
 mod scal_match;
 pub mod shared;
 mod vec_match;
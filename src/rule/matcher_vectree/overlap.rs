@@ -0,0 +1,139 @@
+//! Decide whether two patterns can match the same sequence, in which case
+//! the order of the rules determines the result.
+
+use hashbrown::HashMap;
+
+use super::shared::{AnyMatcher, ScalMatcher, VecMatcher};
+use crate::rule::prepare_rule::is_padding;
+
+/// A pattern flattened into a sequence of single-token and variable-length
+/// slots
+#[derive(Clone, Copy)]
+enum Item<'a> {
+  Scal(&'a ScalMatcher),
+  /// Any one token, the required first token of a nonzero vectorial
+  Any,
+  /// Any sequence of tokens
+  Star,
+}
+
+fn linearize_vec<'a>(matcher: &'a VecMatcher, out: &mut Vec<Item<'a>>) {
+  match matcher {
+    VecMatcher::Placeh { key, .. } if is_padding(key) => (),
+    VecMatcher::Placeh { nonzero, .. } => {
+      if *nonzero {
+        out.push(Item::Any)
+      }
+      out.push(Item::Star)
+    },
+    VecMatcher::Scan { left, sep, right, .. } => {
+      linearize_vec(left, out);
+      out.extend(sep.iter().map(Item::Scal));
+      linearize_vec(right, out)
+    },
+    VecMatcher::Middle { left, left_sep, mid, right_sep, right, .. } => {
+      linearize_vec(left, out);
+      out.extend(left_sep.iter().map(Item::Scal));
+      linearize_vec(mid, out);
+      out.extend(right_sep.iter().map(Item::Scal));
+      linearize_vec(right, out)
+    },
+  }
+}
+
+fn linearize(matcher: &AnyMatcher) -> Vec<Item<'_>> {
+  match matcher {
+    AnyMatcher::Scalar(scalv) => scalv.iter().map(Item::Scal).collect(),
+    AnyMatcher::Vec { left, mid, right } => {
+      let mut out = left.iter().map(Item::Scal).collect::<Vec<_>>();
+      linearize_vec(mid, &mut out);
+      out.extend(right.iter().map(Item::Scal));
+      out
+    },
+  }
+}
+
+fn scal_overlap(a: &ScalMatcher, b: &ScalMatcher) -> bool {
+  match (a, b) {
+    (ScalMatcher::Placeh { name_only: false, .. }, _)
+    | (_, ScalMatcher::Placeh { name_only: false, .. }) => true,
+    (ScalMatcher::Placeh { .. }, ScalMatcher::Placeh { .. } | ScalMatcher::Name(_))
+    | (ScalMatcher::Name(_), ScalMatcher::Placeh { .. }) => true,
+    (ScalMatcher::Name(n1), ScalMatcher::Name(n2)) => n1 == n2,
+    (ScalMatcher::Atom(a1), ScalMatcher::Atom(a2)) => a1.run().0.parser_eq(&*a2.run().0),
+    (ScalMatcher::S(c1, b1), ScalMatcher::S(c2, b2)) => c1 == c2 && any_overlap(b1, b2),
+    (ScalMatcher::Lambda(arg1, b1), ScalMatcher::Lambda(arg2, b2)) =>
+      any_overlap(arg1, arg2) && any_overlap(b1, b2),
+    _ => false,
+  }
+}
+
+/// Whether some sequence is matched by both item lists in full
+fn seq_overlap(a: &[Item], b: &[Item]) -> bool {
+  fn go(
+    a: &[Item],
+    b: &[Item],
+    i: usize,
+    j: usize,
+    memo: &mut HashMap<(usize, usize), bool>,
+  ) -> bool {
+    if let Some(known) = memo.get(&(i, j)) {
+      return *known;
+    }
+    let result = match (a.get(i), b.get(j)) {
+      (None, None) => true,
+      (Some(Item::Star), _) =>
+        go(a, b, i + 1, j, memo) || (j < b.len() && go(a, b, i, j + 1, memo)),
+      (_, Some(Item::Star)) =>
+        go(a, b, i, j + 1, memo) || (i < a.len() && go(a, b, i + 1, j, memo)),
+      (Some(Item::Scal(x)), Some(Item::Scal(y))) =>
+        scal_overlap(x, y) && go(a, b, i + 1, j + 1, memo),
+      (Some(_), Some(_)) => go(a, b, i + 1, j + 1, memo),
+      _ => false,
+    };
+    memo.insert((i, j), result);
+    result
+  }
+  go(a, b, 0, 0, &mut HashMap::new())
+}
+
+/// Whether both matchers match some sequence in full. Generated padding is
+/// ignored, otherwise any two rules would match a sequence that contains both
+/// of their patterns.
+pub(super) fn any_overlap(a: &AnyMatcher, b: &AnyMatcher) -> bool {
+  seq_overlap(&linearize(a), &linearize(b))
+}
+
+#[cfg(test)]
+mod test {
+  use intern_all::i;
+
+  use super::super::build::mk_any;
+  use super::any_overlap;
+  use crate::location::SourceRange;
+  use crate::name::Sym;
+  use crate::parse::parsed::{Clause, PHClass, Placeholder};
+  use crate::rule::matcher::RuleExpr;
+  use crate::sym;
+
+  fn ph(name: &str, class: PHClass) -> RuleExpr {
+    Clause::Placeh(Placeholder { name: i(name), class }).into_expr(SourceRange::mock())
+  }
+
+  fn vec(name: &str) -> RuleExpr { ph(name, PHClass::Vec { nonzero: true, prio: 0 }) }
+
+  fn name(n: Sym) -> RuleExpr { Clause::Name(n).into_expr(SourceRange::mock()) }
+
+  #[test]
+  fn test_overlap() {
+    let plus = [vec("lhs"), name(sym!(std::number::+)), vec("rhs")];
+    let minus = [vec("lhs"), name(sym!(std::number::-)), vec("rhs")];
+    let neg = [name(sym!(std::number::-)), ph("x", PHClass::Scalar)];
+    let pat = |p: &[RuleExpr]| mk_any(p);
+    // `a + b - c` is matched by both
+    assert!(any_overlap(&pat(&plus), &pat(&minus)));
+    // `- x` is too short for the binary minus
+    assert!(!any_overlap(&pat(&neg), &pat(&minus)));
+    assert!(!any_overlap(&pat(&neg), &pat(&plus)));
+  }
+}
@@ -1,13 +1,14 @@
 //! Datastructures for cached pattern
 
 use std::fmt;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use intern_all::Tok;
 use itertools::Itertools;
 
 use super::any_match::any_match;
 use super::build::mk_any;
+use super::overlap::any_overlap;
 use crate::foreign::atom::AtomGenerator;
 use crate::name::Sym;
 use crate::parse::parsed::PType;
@@ -59,7 +60,7 @@ pub(super) enum AnyMatcher {
   Vec { left: Vec<ScalMatcher>, mid: VecMatcher, right: Vec<ScalMatcher> },
 }
 impl Matcher for AnyMatcher {
-  fn new(pattern: Rc<Vec<RuleExpr>>) -> Self { mk_any(&pattern) }
+  fn new(pattern: Arc<Vec<RuleExpr>>) -> Self { mk_any(&pattern) }
 
   fn apply<'a>(
     &self,
@@ -126,7 +127,7 @@ impl fmt::Display for AnyMatcher {
 /// vectorial placeholders and handles the scalars on leaves.
 pub struct VectreeMatcher(AnyMatcher);
 impl Matcher for VectreeMatcher {
-  fn new(pattern: Rc<Vec<RuleExpr>>) -> Self { Self(AnyMatcher::new(pattern)) }
+  fn new(pattern: Arc<Vec<RuleExpr>>) -> Self { Self(AnyMatcher::new(pattern)) }
 
   fn apply<'a>(
     &self,
@@ -136,6 +137,10 @@ impl Matcher for VectreeMatcher {
     self.0.apply(source, save_loc)
   }
 }
+impl VectreeMatcher {
+  /// Whether the two rule patterns can match the same sequence
+  pub fn overlaps(&self, other: &Self) -> bool { any_overlap(&self.0, &other.0) }
+}
 impl fmt::Display for VectreeMatcher {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
 }
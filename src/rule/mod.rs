@@ -1,7 +1,10 @@
 //! Substitution rule processing
+mod cycle;
+mod index;
 pub mod matcher;
 pub mod matcher_vectree;
 mod prepare_rule;
+pub mod proc_macro;
 pub mod repository;
 pub mod rule_error;
 mod state;
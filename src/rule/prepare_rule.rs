@@ -8,27 +8,40 @@ use super::vec_attrs::vec_attrs;
 use crate::parse::parsed::{Clause, PHClass, Placeholder};
 use crate::pipeline::project::ProjRule;
 
+/// Name of the placeholder [prepare_rule] adds in front of a pattern that
+/// doesn't start with a vectorial
+#[must_use]
+pub fn prefix_name() -> Tok<String> { i!(str: "__gen__orchid__rule__prefix") }
+
+/// Name of the placeholder [prepare_rule] adds after a pattern that doesn't
+/// end with a vectorial
+#[must_use]
+pub fn suffix_name() -> Tok<String> { i!(str: "__gen__orchid__rule__suffix") }
+
+/// Whether a placeholder was added by [prepare_rule] rather than the author
+/// of the rule
+#[must_use]
+pub fn is_padding(key: &Tok<String>) -> bool { *key == prefix_name() || *key == suffix_name() }
+
 /// Ensure that the rule's source begins and ends with a vectorial without
 /// changing its meaning
 #[must_use]
 fn pad(rule: ProjRule) -> ProjRule {
-  let prefix_name = i!(str: "__gen__orchid__rule__prefix");
-  let suffix_name = i!(str: "__gen__orchid__rule__suffix");
   let class: PHClass = PHClass::Vec { nonzero: false, prio: 0 };
-  let ProjRule { comments, pattern, prio, template } = rule;
+  let ProjRule { comments, pattern, prio, template, roles } = rule;
   let rule_head = pattern.first().expect("Pattern can never be empty!");
   let rule_tail = pattern.last().unwrap();
   let prefix = vec_attrs(rule_head).is_none().then(|| {
-    Clause::Placeh(Placeholder { name: prefix_name, class })
+    Clause::Placeh(Placeholder { name: prefix_name(), class })
       .into_expr(rule_head.range.map_range(|r| r.start..r.start))
   });
   let suffix = vec_attrs(rule_tail).is_none().then(|| {
-    Clause::Placeh(Placeholder { name: suffix_name, class })
+    Clause::Placeh(Placeholder { name: suffix_name(), class })
       .into_expr(rule_tail.range.map_range(|r| r.start..r.start))
   });
   let pattern = prefix.iter().cloned().chain(pattern).chain(suffix.clone()).collect();
   let template = prefix.into_iter().chain(template).chain(suffix).collect();
-  ProjRule { comments, prio, pattern, template }
+  ProjRule { comments, prio, pattern, template, roles }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
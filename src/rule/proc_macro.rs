@@ -0,0 +1,91 @@
+//! Substitution rules whose output is computed by a Rust function. These are
+//! contributed by a [crate::facade::system::System] and scheduled by priority
+//! alongside the declarative rules.
+
+use std::fmt;
+use std::sync::Arc;
+
+use intern_all::Tok;
+use ordered_float::NotNan;
+use trait_set::trait_set;
+
+use super::matcher::RuleExpr;
+use crate::location::SourceRange;
+
+/// The input of a procedural macro
+pub struct ProcMacroCall<'a> {
+  /// The tokens matched by the pattern
+  pub matched: &'a [RuleExpr],
+  /// The values of the placeholders in the pattern, ordered by name
+  pub bindings: Vec<(Tok<String>, Vec<RuleExpr>)>,
+  /// Location covering the matched tokens, to be given to new tokens
+  pub range: SourceRange,
+}
+
+trait_set! {
+  /// Function that computes the replacement for the tokens matched by a
+  /// procedural macro
+  pub trait ProcMacroFn = for<'a> Fn(ProcMacroCall<'a>) -> Vec<RuleExpr> + Send + Sync;
+}
+
+/// A substitution rule implemented in Rust. The output of the function is
+/// treated like the template of a declarative rule, so it may contain the
+/// placeholders of the pattern, and names that also appear in the pattern
+/// take the location of the matched name.
+#[derive(Clone)]
+pub struct ProcMacro {
+  /// Sequence of tokens to replace
+  pub pattern: Vec<RuleExpr>,
+  /// Priority of the rule, on the same scale as declarative rules
+  pub prio: NotNan<f64>,
+  /// Comments associated with the rule, shown when tracing
+  pub comments: Vec<Arc<String>>,
+  /// Function computing the replacement
+  pub run: Arc<dyn ProcMacroFn>,
+}
+impl ProcMacro {
+  /// Create a procedural macro with no comments
+  pub fn new(pattern: Vec<RuleExpr>, prio: NotNan<f64>, run: impl ProcMacroFn + 'static) -> Self {
+    Self { pattern, prio, comments: Vec::new(), run: Arc::new(run) }
+  }
+}
+impl fmt::Debug for ProcMacro {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ProcMacro")
+      .field("pattern", &self.pattern)
+      .field("prio", &self.prio)
+      .field("comments", &self.comments)
+      .finish_non_exhaustive()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use intern_all::i;
+  use ordered_float::NotNan;
+
+  use super::ProcMacro;
+  use crate::error::Reporter;
+  use crate::location::SourceRange;
+  use crate::parse::parsed::{Clause, PHClass, PType, Placeholder};
+  use crate::rule::repository::Repo;
+  use crate::sym;
+
+  #[test]
+  fn test_proc_macro() {
+    let ex = |c: Clause| c.into_expr(SourceRange::mock());
+    let ph = |name: &str| Clause::Placeh(Placeholder { name: i(name), class: PHClass::Scalar });
+    let pattern = vec![ex(Clause::Name(sym!(test::swap))), ex(ph("a")), ex(ph("b"))];
+    let swap = ProcMacro::new(pattern, NotNan::new(1.0).unwrap(), move |call| {
+      assert_eq!(call.matched.len(), 3, "the padding is not passed to the procedure");
+      vec![ex(ph("b")), ex(ph("a"))]
+    });
+    let repo = Repo::new(Vec::new(), vec![swap], &Reporter::new());
+    let names = [sym!(test::x), sym!(test::swap), sym!(test::y), sym!(test::z), sym!(test::w)];
+    let code = ex(Clause::S(PType::Par, Arc::new(names.map(|n| ex(Clause::Name(n))).to_vec())));
+    let out = repo.step(&code).expect("The rule should match");
+    assert_eq!(out.to_string(), "(test::x test::z test::y test::w)");
+  }
+}
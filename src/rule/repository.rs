@@ -1,40 +1,192 @@
 //! Collects, prioritizes and executes rules.
 
+use std::collections::VecDeque;
 use std::fmt;
-use std::rc::Rc;
+use std::slice;
+use std::sync::Arc;
 
 use hashbrown::HashSet;
+use intern_all::Tok;
 use itertools::Itertools;
 use ordered_float::NotNan;
 
+use super::cycle::{CycleDetector, MAX_PERIOD};
+use super::index::{Glossary, RuleIndex, Shape};
 use super::matcher::{Matcher, RuleExpr};
 use super::matcher_vectree::shared::VectreeMatcher;
-use super::prepare_rule::prepare_rule;
-use super::state::apply_exprv;
+use super::prepare_rule::{prefix_name, prepare_rule, suffix_name};
+use super::proc_macro::{ProcMacro, ProcMacroCall};
+use super::state::{apply_exprv, State};
 use super::update_first_seq;
-use crate::error::Reporter;
+use crate::error::{ErrorPosition, ProjectError, ProjectErrorObj, Reporter};
+use crate::location::SourceRange;
 use crate::name::Sym;
-use crate::parse::numeric::print_nat16;
+use crate::parse::lexer::print_prio;
+use crate::parse::parsed::Clause;
 use crate::pipeline::project::ProjRule;
 
+/// Where a rule came from, for tracing and error reporting
+#[derive(Clone, Debug)]
+pub struct RuleOrigin {
+  /// Priority of the rule
+  pub prio: NotNan<f64>,
+  /// Source code of the pattern
+  pub location: SourceRange,
+  /// Comments associated with the rule
+  pub comments: Vec<Arc<String>>,
+}
+impl RuleOrigin {
+  fn new(rule: &ProjRule) -> Self {
+    let ProjRule { pattern, prio, comments, .. } = rule;
+    Self { prio: *prio, location: span(pattern), comments: comments.clone() }
+  }
+}
+
+/// Range covering a nonempty sequence, or its first element if the sequence
+/// spans several files
+fn span(exprv: &[RuleExpr]) -> SourceRange {
+  let first = &exprv.first().expect("Sequence is never empty").range;
+  let last = &exprv.last().expect("Sequence is never empty").range;
+  match first.path() == last.path() {
+    true => first.map_range(|r| r.start..last.range().end.max(r.end)),
+    false => first.clone(),
+  }
+}
+impl fmt::Display for RuleOrigin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "rule at {} with priority {}", self.location, print_prio(self.prio))?;
+    for comment in self.comments.iter() {
+      write!(f, "\n  --[{}]--", comment.trim())?;
+    }
+    Ok(())
+  }
+}
+
+/// Detailed account of a single substitution
+#[derive(Clone, Debug)]
+pub struct RuleTrace {
+  /// The rule that fired
+  pub rule: RuleOrigin,
+  /// The sequence the pattern matched
+  pub matched: Vec<RuleExpr>,
+  /// The values of the placeholders in the pattern
+  pub bindings: Vec<(Tok<String>, Vec<RuleExpr>)>,
+  /// The matched tokens that were given roles by the pattern
+  pub roles: Vec<TokenRole>,
+}
+impl fmt::Display for RuleTrace {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}\nmatched: {}", self.rule, self.matched.iter().join(" "))?;
+    for (name, value) in self.bindings.iter() {
+      write!(f, "\n  ${name} = {}", value.iter().join(" "))?;
+    }
+    for TokenRole { role, range } in self.roles.iter() {
+      write!(f, "\n  @{role} at {range}")?;
+    }
+    Ok(())
+  }
+}
+
+/// A rule that fired and the tokens it replaced
+#[derive(Clone, Debug)]
+pub struct FiredRule {
+  /// The rule
+  pub rule: RuleOrigin,
+  /// Location of the first replaced token
+  pub matched: SourceRange,
+}
+
+/// A token in the source that filled a slot annotated with a role
+#[derive(Clone, Debug)]
+pub struct TokenRole {
+  /// The role written in the pattern
+  pub role: Tok<String>,
+  /// Location of the token
+  pub range: SourceRange,
+}
+
+/// The slot in a pattern a role is attached to
+#[derive(Clone, Debug)]
+enum RoleSlot {
+  Placeh(Tok<String>),
+  Name(Sym),
+}
+
+/// Result of [Repository::long_step]
+#[derive(Clone, Debug)]
+pub struct LongStep {
+  /// The final tree
+  pub expr: RuleExpr,
+  /// The number of iterations left to the limit
+  pub leftover: usize,
+  /// The rules that fired last, oldest first
+  pub history: Vec<RuleOrigin>,
+  /// If the expression returned to an earlier state, the steps that led back
+  /// to it. The execution stops when this happens.
+  pub cycle: Option<Vec<FiredRule>>,
+  /// The tokens that were given roles by the rules that fired
+  pub roles: Vec<TokenRole>,
+}
+
 #[derive(Debug)]
 pub(super) struct CachedRule<M: Matcher> {
   matcher: M,
   pattern: Vec<RuleExpr>,
-  pat_glossary: HashSet<Sym>,
+  shape: Shape,
   template: Vec<RuleExpr>,
   save_location: HashSet<Sym>,
+  origin: RuleOrigin,
+  roles: Vec<(RoleSlot, Tok<String>)>,
+  /// For procedural macros, the function that generates the tokens between
+  /// the padding in the template
+  procedure: Option<ProcMacro>,
+}
+impl<M: Matcher> CachedRule<M> {
+  /// Generate the replacement for a matched sequence
+  fn apply(&self, exprv: &[RuleExpr], state: &State) -> Vec<RuleExpr> {
+    let Some(procedure) = &self.procedure else { return apply_exprv(&self.template, state) };
+    let prefix = state.ph_len(&prefix_name()).unwrap_or(0);
+    let suffix = state.ph_len(&suffix_name()).unwrap_or(0);
+    let matched = &exprv[prefix..exprv.len() - suffix];
+    let range = match matched.is_empty() {
+      true => span(exprv),
+      false => span(matched),
+    };
+    let output = (procedure.run)(ProcMacroCall { matched, bindings: state.bindings(), range });
+    let padded = matches!(
+      self.template.first().map(|e| &e.value),
+      Some(Clause::Placeh(ph)) if ph.name == prefix_name()
+    );
+    let mut template = self.template.clone();
+    template.splice(padded as usize..padded as usize, output);
+    apply_exprv(&template, state)
+  }
+
+  /// Find the tokens that filled the slots with roles in a match
+  fn roles(&self, state: &State) -> Vec<TokenRole> {
+    let mut out = Vec::new();
+    for (slot, role) in self.roles.iter() {
+      let ranges = match slot {
+        RoleSlot::Placeh(key) => match &state.value(key)[..] {
+          [] => Vec::new(),
+          value => vec![span(value)],
+        },
+        RoleSlot::Name(name) => state.name_locations(name).to_vec(),
+      };
+      out.extend(ranges.into_iter().map(|range| TokenRole { role: role.clone(), range }))
+    }
+    out
+  }
 }
 
 impl<M: fmt::Display + Matcher> fmt::Display for CachedRule<M> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     let patterns = self.pattern.iter().join(" ");
-    write!(
-      f,
-      "{patterns} is matched by {} and generates {}",
-      self.matcher,
-      self.template.iter().map(|e| e.to_string()).join(" ")
-    )
+    let template = match &self.procedure {
+      Some(_) => "the output of a procedure".to_string(),
+      None => self.template.iter().map(|e| e.to_string()).join(" "),
+    };
+    write!(f, "{patterns} is matched by {} and generates {template}", self.matcher)
   }
 }
 
@@ -42,48 +194,114 @@ impl<M: fmt::Display + Matcher> fmt::Display for CachedRule<M> {
 ///
 /// Manages a priority queue of rules and offers functions to apply them. The
 /// rules are stored in an optimized structure but the repository is generic
-/// over the implementation of this optimized form.
+/// over the implementation of this optimized form. An index over the names in
+/// the patterns selects the rules that may match at all.
 ///
 /// If you don't know what to put in the generic parameter, use [Repo]
 pub struct Repository<M: Matcher> {
   cache: Vec<(CachedRule<M>, NotNan<f64>)>,
+  index: RuleIndex,
 }
 impl<M: Matcher> Repository<M> {
-  /// Build a new repository to hold the given set of rules
-  pub fn new(mut rules: Vec<ProjRule>, reporter: &Reporter) -> Self {
-    rules.sort_by_key(|r| -r.prio);
+  /// Build a new repository to hold the given set of declarative and
+  /// procedural rules
+  pub fn new(rules: Vec<ProjRule>, procs: Vec<ProcMacro>, reporter: &Reporter) -> Self {
+    let procs = procs.into_iter().map(|p| {
+      let ProcMacro { pattern, prio, comments, .. } = p.clone();
+      (ProjRule { pattern, prio, template: Vec::new(), comments, roles: Vec::new() }, Some(p))
+    });
+    let mut rules = rules.into_iter().map(|r| (r, None)).chain(procs).collect_vec();
+    rules.sort_by_key(|(r, _)| -r.prio);
     let cache = rules
       .into_iter()
-      .filter_map(|r| {
-        let ProjRule { pattern, prio, template, comments: _ } = prepare_rule(r.clone())
+      .filter_map(|(r, procedure)| {
+        let origin = RuleOrigin::new(&r);
+        let ProjRule { pattern, prio, template, comments: _, roles } = prepare_rule(r.clone())
           .inspect_err(|e| reporter.report(e.clone().into_project(&r)))
           .ok()?;
-        let mut pat_glossary = HashSet::new();
-        pat_glossary.extend(pattern.iter().flat_map(|e| e.value.collect_names().into_iter()));
+        let shape = Shape::new(&pattern);
         let mut tpl_glossary = HashSet::new();
         tpl_glossary.extend(template.iter().flat_map(|e| e.value.collect_names().into_iter()));
-        let save_location = pat_glossary.intersection(&tpl_glossary).cloned().collect();
-        let matcher = M::new(Rc::new(pattern.clone()));
-        let prep = CachedRule { matcher, pattern, template, pat_glossary, save_location };
+        // the output of a procedure is not known in advance
+        let mut save_location: HashSet<Sym> = match procedure {
+          Some(_) => shape.names.clone(),
+          None => shape.names.intersection(&tpl_glossary).cloned().collect(),
+        };
+        let roles = (roles.into_iter())
+          .filter_map(|(range, role)| {
+            let slot = pattern.iter().find_map(|e| {
+              e.search_all(&mut |e| match &e.value {
+                _ if e.range != range => None,
+                Clause::Name(n) => Some(RoleSlot::Name(n.clone())),
+                Clause::Placeh(ph) => Some(RoleSlot::Placeh(ph.name.clone())),
+                _ => None,
+              })
+            })?;
+            if let RoleSlot::Name(n) = &slot {
+              save_location.insert(n.clone());
+            }
+            Some((slot, role))
+          })
+          .collect();
+        let matcher = M::new(Arc::new(pattern.clone()));
+        let prep =
+          CachedRule { matcher, pattern, template, shape, save_location, origin, roles, procedure };
         Some((prep, prio))
       })
       .collect::<Vec<_>>();
-    Self { cache }
+    let index = RuleIndex::new(cache.iter().map(|(r, _)| &r.shape));
+    Self { cache, index }
   }
 
   /// Attempt to run each rule in priority order once
   #[must_use]
   pub fn step(&self, code: &RuleExpr) -> Option<RuleExpr> {
-    let glossary = code.value.collect_names();
-    for (rule, _) in self.cache.iter() {
-      if !rule.pat_glossary.is_subset(&glossary) {
+    self.step_glossary(code, &mut Glossary::new(slice::from_ref(code)), &mut |_, _, _| ())
+  }
+
+  /// Like [Repository::step], but also report which rule fired, what it
+  /// matched and the values of its placeholders
+  #[must_use]
+  pub fn step_traced(&self, code: &RuleExpr) -> Option<(RuleExpr, RuleTrace)> {
+    let mut trace = None;
+    let mut glossary = Glossary::new(slice::from_ref(code));
+    let result = self.step_glossary(code, &mut glossary, &mut |idx, matched, state| {
+      let (rule, _) = &self.cache[idx];
+      trace = Some(RuleTrace {
+        rule: rule.origin.clone(),
+        matched: matched.to_vec(),
+        bindings: state.bindings(),
+        roles: rule.roles(state),
+      })
+    })?;
+    Some((result, trace.expect("Set when a rule fires")))
+  }
+
+  /// Attempt to run each candidate rule in priority order once, and update the
+  /// glossary of the expression with the substitution. The callback receives
+  /// the index of the rule that fired, the matched sequence and the bindings.
+  fn step_glossary(
+    &self,
+    code: &RuleExpr,
+    glossary: &mut Glossary,
+    on_fire: &mut impl FnMut(usize, &[RuleExpr], &State),
+  ) -> Option<RuleExpr> {
+    for idx in self.index.candidates(glossary) {
+      let (rule, _) = &self.cache[idx];
+      if !rule.shape.admits(glossary) {
         continue;
       }
       let product = update_first_seq::expr(code, &mut |exprv| {
+        if !rule.shape.may_match(&exprv) {
+          return None;
+        }
         let save_loc = |n| rule.save_location.contains(&n);
         let state = rule.matcher.apply(exprv.as_slice(), &save_loc)?;
-        let result = apply_exprv(&rule.template, &state);
-        Some(Rc::new(result))
+        let result = Arc::new(rule.apply(&exprv, &state));
+        on_fire(idx, &exprv, &state);
+        glossary.remove(&exprv);
+        glossary.add(&result);
+        Some(result)
       });
       if let Some(newcode) = product {
         return Some(newcode);
@@ -96,39 +314,102 @@ impl<M: Matcher> Repository<M> {
   /// rules match. WARNING: this function might not terminate
   #[must_use]
   pub fn pass(&self, code: &RuleExpr) -> Option<RuleExpr> {
-    if let Some(mut processed) = self.step(code) {
-      while let Some(out) = self.step(&processed) {
-        processed = out
-      }
-      Some(processed)
-    } else {
-      None
+    let mut glossary = Glossary::new(slice::from_ref(code));
+    let mut processed = self.step_glossary(code, &mut glossary, &mut |_, _, _| ())?;
+    while let Some(out) = self.step_glossary(&processed, &mut glossary, &mut |_, _, _| ()) {
+      processed = out
     }
+    Some(processed)
   }
 
-  /// Attempt to run each rule in priority order `limit` times. Returns
-  /// the final tree and the number of iterations left to the limit.
+  /// Attempt to run each rule in priority order `limit` times, remembering
+  /// the last `history` rules that fired. Stops early if the expression
+  /// returns to a state it was already in, since it would then never halt.
   #[must_use]
-  pub fn long_step(&self, code: &RuleExpr, mut limit: usize) -> (RuleExpr, usize) {
-    if limit == 0 {
-      return (code.clone(), 0);
-    }
-    if let Some(mut processed) = self.step(code) {
+  pub fn long_step(&self, code: &RuleExpr, mut limit: usize, history: usize) -> LongStep {
+    // enough of the latest rules to list the history and any detected cycle
+    let capacity = history.max(MAX_PERIOD);
+    let mut fired = VecDeque::with_capacity(capacity);
+    let mut step = 0;
+    let mut roles = Vec::new();
+    let mut states = CycleDetector::new(code);
+    let mut glossary = Glossary::new(slice::from_ref(code));
+    let mut expr = code.clone();
+    let mut cycle = None;
+    while 0 < limit {
+      let mut on_fire = |idx: usize, matched: &[RuleExpr], state: &State| {
+        if fired.len() == capacity {
+          fired.pop_front();
+        }
+        fired.push_back((idx, matched[0].range.clone()));
+        roles.extend(self.cache[idx].0.roles(state))
+      };
+      match self.step_glossary(&expr, &mut glossary, &mut on_fire) {
+        None => break,
+        Some(out) => expr = out,
+      }
       limit -= 1;
-      if limit == 0 {
-        return (processed, 0);
+      step += 1;
+      if let Some(prev) = states.visit(&expr, step) {
+        cycle = Some(fired.iter().skip(fired.len() - (step - prev)).cloned().collect_vec());
+        break;
       }
-      while let Some(out) = self.step(&processed) {
-        limit -= 1;
-        if limit == 0 {
-          return (out, 0);
+    }
+    let origin = |idx: usize| self.cache[idx].0.origin.clone();
+    let cycle = cycle.map(|steps| {
+      let steps = steps.into_iter();
+      steps.map(|(idx, matched)| FiredRule { rule: origin(idx), matched }).collect()
+    });
+    let history = fired.iter().skip(fired.len().saturating_sub(history)).map(|(i, _)| origin(*i));
+    LongStep { expr, leftover: limit, history: history.collect(), cycle, roles }
+  }
+}
+
+impl Repository<VectreeMatcher> {
+  /// Find pairs of rules with equal priority that can match the same
+  /// sequence. Which of them fires depends on the order in which they were
+  /// loaded, so this is probably unintended. Only pairs where at least one
+  /// rule passes the filter are reported.
+  #[must_use]
+  pub fn lint(&self, filter: impl Fn(&RuleOrigin) -> bool) -> Vec<ProjectErrorObj> {
+    let mut warnings = Vec::new();
+    for (_, group) in &self.cache.iter().group_by(|(_, prio)| *prio) {
+      let group = group.map(|(rule, _)| rule).collect_vec();
+      for (i, first) in group.iter().enumerate() {
+        for second in &group[i + 1..] {
+          let relevant = filter(&first.origin) || filter(&second.origin);
+          if relevant && first.matcher.overlaps(&second.matcher) {
+            let (first, second) = (first.origin.clone(), second.origin.clone());
+            warnings.push(OverlappingRules { first, second }.pack())
+          }
         }
-        processed = out;
       }
-      (processed, limit)
-    } else {
-      (code.clone(), limit)
     }
+    warnings
+  }
+}
+
+/// Two rules of equal priority that may match the same sequence
+#[derive(Debug)]
+pub struct OverlappingRules {
+  /// The rule that is tried first
+  pub first: RuleOrigin,
+  /// The rule that is tried second
+  pub second: RuleOrigin,
+}
+impl ProjectError for OverlappingRules {
+  const DESCRIPTION: &'static str = "Rules of equal priority match the same sequence";
+
+  fn message(&self) -> String {
+    let prio = print_prio(self.first.prio);
+    format!("Both rules have priority {prio}, so which one fires depends on the load order")
+  }
+
+  fn positions(&self) -> impl IntoIterator<Item = ErrorPosition> + '_ {
+    [
+      ErrorPosition { origin: self.first.location.origin(), message: Some("tried first".into()) },
+      ErrorPosition { origin: self.second.location.origin(), message: Some("tried second".into()) },
+    ]
   }
 }
 
@@ -145,8 +426,8 @@ impl<M: fmt::Display + Matcher> fmt::Display for Repository<M> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     writeln!(f, "Repository[")?;
     for (rule, p) in self.cache.iter() {
-      let prio = print_nat16(*p);
-      let deps = rule.pat_glossary.iter().join(", ");
+      let prio = print_prio(*p);
+      let deps = rule.shape.names.iter().join(", ");
       writeln!(f, "  priority: {prio}\tdependencies: [{deps}]")?;
       writeln!(f, "    {rule}")?;
     }
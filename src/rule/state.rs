@@ -1,9 +1,10 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use hashbrown::HashMap;
 use intern_all::Tok;
 
 use super::matcher::RuleExpr;
+use super::prepare_rule::is_padding;
 use crate::location::SourceRange;
 use crate::name::Sym;
 use crate::parse::parsed::{Clause, Expr, PHClass, Placeholder};
@@ -42,6 +43,30 @@ impl<'a> State<'a> {
   pub fn from_name(name: Sym, location: SourceRange) -> Self {
     Self { name_locations: HashMap::from([(name, vec![location])]), placeholders: HashMap::new() }
   }
+  /// The tokens matched by a placeholder
+  pub fn value(&self, key: &Tok<String>) -> Vec<RuleExpr> {
+    match self.placeholders.get(key) {
+      None => Vec::new(),
+      Some(StateEntry::Vec(slc)) => slc.to_vec(),
+      Some(StateEntry::Scalar(item)) => vec![(*item).clone()],
+      Some(StateEntry::Name(n, r)) =>
+        vec![RuleExpr { value: Clause::Name((*n).clone()), range: (*r).clone() }],
+    }
+  }
+  /// The locations of a name matched by the pattern, if it was saved
+  pub fn name_locations(&self, name: &Sym) -> &[SourceRange] {
+    self.name_locations.get(name).map_or(&[], |v| &v[..])
+  }
+  /// The value of every placeholder written by the user, ordered by name. The
+  /// padding added by [super::prepare_rule] is omitted.
+  pub fn bindings(&self) -> Vec<(Tok<String>, Vec<RuleExpr>)> {
+    let mut bindings = (self.placeholders.keys())
+      .filter(|key| !is_padding(key))
+      .map(|key| (key.clone(), self.value(key)))
+      .collect::<Vec<_>>();
+    bindings.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    bindings
+  }
 }
 impl Default for State<'static> {
   fn default() -> Self { Self { name_locations: HashMap::new(), placeholders: HashMap::new() } }
@@ -63,7 +88,7 @@ pub fn apply_expr(template: &RuleExpr, state: &State) -> Vec<RuleExpr> {
     Clause::Atom(_) => vec![template.clone()],
     Clause::S(c, body) => vec![Expr {
       range: range.clone(),
-      value: Clause::S(*c, Rc::new(apply_exprv(body.as_slice(), state))),
+      value: Clause::S(*c, Arc::new(apply_exprv(body.as_slice(), state))),
     }],
     Clause::Placeh(Placeholder { name, class }) => {
       let value = *unwrap_or!(state.placeholders.get(name);
@@ -81,8 +106,8 @@ pub fn apply_expr(template: &RuleExpr, state: &State) -> Vec<RuleExpr> {
     Clause::Lambda(arg, body) => vec![Expr {
       range: range.clone(),
       value: Clause::Lambda(
-        Rc::new(apply_exprv(arg, state)),
-        Rc::new(apply_exprv(&body[..], state)),
+        Arc::new(apply_exprv(arg, state)),
+        Arc::new(apply_exprv(&body[..], state)),
       ),
     }],
   }
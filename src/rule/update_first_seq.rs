@@ -1,5 +1,5 @@
 use std::iter;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::matcher::RuleExpr;
 use crate::parse::parsed::{Clause, Expr};
@@ -8,18 +8,18 @@ use crate::parse::parsed::{Clause, Expr};
 /// some vec then replace the sibling list with that vec and return true
 /// return false if pred never returned some
 #[must_use]
-pub fn exprv<F: FnMut(Rc<Vec<RuleExpr>>) -> Option<Rc<Vec<RuleExpr>>>>(
-  input: Rc<Vec<RuleExpr>>,
+pub fn exprv<F: FnMut(Arc<Vec<RuleExpr>>) -> Option<Arc<Vec<RuleExpr>>>>(
+  input: Arc<Vec<RuleExpr>>,
   pred: &mut F,
-) -> Option<Rc<Vec<RuleExpr>>> {
+) -> Option<Arc<Vec<RuleExpr>>> {
   if let Some(v) = pred(input.clone()) {
     return Some(v);
   }
-  replace_first(input.as_ref(), |ex| expr(ex, pred)).map(|i| Rc::new(i.collect()))
+  replace_first(input.as_ref(), |ex| expr(ex, pred)).map(|i| Arc::new(i.collect()))
 }
 
 #[must_use]
-pub fn expr<F: FnMut(Rc<Vec<RuleExpr>>) -> Option<Rc<Vec<RuleExpr>>>>(
+pub fn expr<F: FnMut(Arc<Vec<RuleExpr>>) -> Option<Arc<Vec<RuleExpr>>>>(
   input: &RuleExpr,
   pred: &mut F,
 ) -> Option<RuleExpr> {
@@ -27,7 +27,7 @@ pub fn expr<F: FnMut(Rc<Vec<RuleExpr>>) -> Option<Rc<Vec<RuleExpr>>>>(
 }
 
 #[must_use]
-pub fn clause<F: FnMut(Rc<Vec<RuleExpr>>) -> Option<Rc<Vec<RuleExpr>>>>(
+pub fn clause<F: FnMut(Arc<Vec<RuleExpr>>) -> Option<Arc<Vec<RuleExpr>>>>(
   c: &Clause,
   pred: &mut F,
 ) -> Option<Clause> {